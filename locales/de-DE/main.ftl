@@ -1,20 +1,23 @@
 activate-chatbridge-inline = Aktiviere Chatbridge
-start-server = Ich starte den Server. Wenn du die Chatbridge aktivieren möchtest, dann betätige den Knopf unter der Nachricht.
-start-server-without-button = Ich starte den Server.
-server-started-now = Der Server ist nun gestartet.
-server-started-unknown = Der Server wurde gestartet, allerdings kann nicht ermittelt werden, ob er nun auch läuft. Dies deutet normalerweise darauf hin, dass der Server nicht erfolgreich gestartet ist. Bitte kontaktiere deinen Serveradministrator und frage nach, was schiefgelaufen ist.
+start-server = Ich starte den Server { $server }. Wenn du die Chatbridge aktivieren möchtest, dann betätige den Knopf unter der Nachricht.
+start-server-without-button = Ich starte den Server { $server }.
+server-started-now = Der Server { $server } ist nun gestartet.
+server-starting-slowly = Der Server braucht länger als sonst zum Starten. Ich sage Bescheid, sobald er bereit ist.
+server-started-late = Der Server { $server } ist nun doch gestartet.
+server-start-failed = Der Server { $server } konnte nicht gestartet werden ({ $status }).
 server-starting-already = Der Server startet bereits.
 server-running-already = Der Server läuft bereits.
-server-not-running = Der Server läuft derzeit nicht.
+server-not-running = Der Server { $server } läuft derzeit nicht.
 server-starting = Der Server startet gerade.
 server-starting-cannot-stop = Der Server startet gerade. Bitte warte, bis der Server vollständig hochgefahren ist, bis du ihn stoppst.
-stop-server = Ich stoppe den Server.
+stop-server = Ich stoppe den Server { $server }.
 server-stopped-externally = Der Server wurde durch einen anderen Chat oder ein externes System gestoppt. Ich deaktiviere die Chatbridge.
 server-running =
     { $currentPlayers ->
-        [0] Der Server läuft gerade, aber niemand ist online.
-        *[other] Der Server läuft gerade und es sind { $currentPlayers } von { $maximumPlayers } Spieler:innen online: { $players }
+        [0] Der Server { $server } läuft gerade, aber niemand ist online.
+        *[other] Der Server { $server } läuft gerade und es sind { $currentPlayers } von { $maxPlayers } Spieler:innen online: { $players }
     }
+server-running-unknown-players = Der Server { $server } läuft gerade, aber ich konnte die Liste der Spieler:innen nicht auslesen.
 chatbridge-activated = Die Chatbridge ist bereits aktiviert.
 chatbridge-activation-not-possible-server-not-running = Der Server läuft gerade nicht, daher kann die Chatbridge nicht gestartet werden.
 activate-chatbridge-after-start = Ok! Ich aktiviere die Chatbridge, sobald der Server fertig hochgefahren ist.
@@ -23,3 +26,87 @@ activate-chatbridge = Ich aktiviere die Chatbridge.
 chatbridge-deactivated = Die Chatbridge ist bereits deaktiviert.
 deactivate-chatbridge = Ich deaktiviere die Chatbridge.
 licence = Dieser Bot ist freie Software! Der Quelltext ist lizenziert unter GPLv3 oder einer späteren Version. Der Quelltext ist abrufbar unter https://github.com/PatrickJosh/minecraft-server-telegram-bot.
+player-joined = { $player } hat das Spiel betreten.
+player-left = { $player } hat das Spiel verlassen.
+player-advancement = { $player } hat den Fortschritt [{ $advancement }] erzielt.
+player-challenge = { $player } hat die Aufgabe [{ $advancement }] gemeistert.
+player-goal = { $player } hat das Ziel [{ $advancement }] erreicht.
+help = Diese Befehle verstehe ich:
+unknown-command = Den Befehl /{ $command } kenne ich nicht.
+invalid-argument = Das Argument „{ $argument }“ verstehe ich nicht.
+too-many-arguments = Dieser Befehl benötigt weniger Argumente.
+command-start-server = Starte den Server
+command-stop-server = Stoppe den Server
+command-status-server = Zeige, ob der Server läuft und wer online ist
+command-enable-chatbridge = Spiegle den Chat zwischen Minecraft und diesem Chat
+command-disable-chatbridge = Beende das Spiegeln des Chats
+command-keep-alive = Lass den Server die angegebene Anzahl Stunden laufen, auch wenn niemand online ist
+command-schedule = Zeige, wann die geplanten Aktionen als Nächstes laufen
+command-licence = Zeige die Lizenz dieses Bots
+command-help = Zeige die Befehle oder beschreibe den angegebenen Befehl
+permission-denied = Das darfst du nicht.
+bot-shutdown = Ich gehe vorübergehend offline, die Chatbridge ist pausiert, bis ich wieder da bin.
+media-photo = Foto
+media-sticker = Sticker
+media-sticker-emoji = Sticker { $emoji }
+media-sticker-set = Aus dem Stickerset { $set }
+media-voice = Sprachnachricht { $duration }
+media-audio = Audio
+media-audio-title = Audio: { $title }
+media-video = Video { $duration }
+media-video-note = Videonachricht { $duration }
+media-animation = GIF
+media-document = Datei
+media-document-name = Datei: { $name }
+media-poll = Umfrage: { $question }
+media-venue = Ort: { $title }
+media-location = Standort
+media-contact = Kontakt: { $name }
+media-dice = Würfel
+telegram-sender = { $name } auf Telegram
+error-command = Etwas ist schiefgelaufen: Ich konnte den Server nicht steuern. Bitte die Administrator:in des Bots, in das Log zu schauen.
+error-rcon = Etwas ist schiefgelaufen: Ich konnte den Server nicht erreichen. Bitte versuche es später noch einmal.
+error-message-too-long = Diese Nachricht ist zu lang für Minecraft. Bitte schicke eine kürzere.
+error-log = Etwas ist schiefgelaufen: Ich konnte das Log des Servers nicht lesen. Bitte die Administrator:in des Bots, sich das anzusehen.
+error-parse = Etwas ist schiefgelaufen: Ich habe die Antwort des Servers nicht verstanden.
+error-telegram = Etwas ist bei der Kommunikation mit Telegram schiefgelaufen. Bitte versuche es später noch einmal.
+error-config = Etwas ist schiefgelaufen: Die Konfiguration des Bots ist ungültig. Bitte die Administrator:in des Bots, sie zu korrigieren.
+startup-loading-mods = ⚙️ Lade Mods…
+startup-upgrading-world = 🔄 Aktualisiere die Welt…
+startup-preparing-level = 🌍 Bereite die Welt vor…
+startup-preparing-spawn = 🌍 Bereite den Spawnbereich vor…
+startup-done = ✅ Fertig ({ $seconds } s)
+startup-ready = ✅ Bereit
+startup-failed = ❌ Fehlgeschlagen
+report-exceptions = Fehler:
+report-log-tail = Letzte Zeilen des Logs:
+server-crashed = 💥 Der Server { $server } wurde unerwartet beendet ({ $reason }).
+watchdog-unreachable = { $checks }-mal in Folge keine Antwort über RCON
+crash-report = Absturzbericht: { $file }
+watchdog-restarting = Ich starte den Server in { $seconds } Sekunden neu.
+watchdog-restarted = Der Server läuft wieder.
+watchdog-restart-limit = Ich habe den Server in der letzten Stunde bereits { $restarts }-mal neu gestartet, daher lasse ich ihn jetzt gestoppt. Bitte die Administrator:in des Servers, sich das anzuschauen.
+
+keep-alive = Ok! Ich stoppe den Server { $hours ->
+    [one] in der nächsten Stunde
+   *[other] in den nächsten { $hours } Stunden
+} nicht, nur weil niemand online ist.
+keep-alive-ended = Ok! Der Server wird wieder gestoppt, sobald eine Weile niemand online war.
+keep-alive-not-needed = Der Server wird ohnehin nicht automatisch gestoppt.
+idle-shutdown = Ich habe den Server { $server } gestoppt, weil { $minutes } Minuten lang niemand online war.
+
+schedule-none = Für diesen Server sind keine Aktionen geplant.
+schedule-upcoming = Die nächsten geplanten Aktionen ({ $timeZone }):
+schedule-action-start = Server starten
+schedule-action-stop = Server stoppen
+schedule-action-restart = Server neu starten
+schedule-action-broadcast = „{ $message }“ an alle senden
+schedule-ran = Geplante Aktion für { $server }: { $action }
+schedule-server-running = Der Server läuft.
+schedule-server-starting-slowly = Der Server braucht länger zum Starten als erwartet.
+schedule-server-start-failed = Der Server konnte nicht gestartet werden: { $status }
+schedule-server-stopped = Der Server ist gestoppt.
+schedule-server-stopping-slowly = Der Server braucht länger zum Stoppen als erwartet.
+schedule-skipped-not-running = Übersprungen, der Server läuft nicht.
+schedule-skipped-starting = Übersprungen, der Server startet gerade.
+schedule-skipped-running = Übersprungen, der Server läuft bereits.
@@ -1,20 +1,23 @@
 activate-chatbridge-inline = Activate chat bridge
-start-server = I start the server. If you want to activate the chat bridge, press the button beneath the message.
-start-server-without-button = I start the server.
-server-started-now = The server is running now.
-server-started-unknown = The server was started, however it cannot be determined if it is running now. This is usually an indication that the server encountered an error during its start. Please contact your admin to ask what went wrong.
+start-server = I start the server { $server }. If you want to activate the chat bridge, press the button beneath the message.
+start-server-without-button = I start the server { $server }.
+server-started-now = The server { $server } is running now.
+server-starting-slowly = The server takes longer to start than usual. I'll let you know when it is ready.
+server-started-late = The server { $server } is running now, after all.
+server-start-failed = The server { $server } could not be started ({ $status }).
 server-starting-already = The server is already starting.
 server-running-already = The server is running already.
-server-not-running = The server is not running currently.
+server-not-running = The server { $server } is not running currently.
 server-starting = The server is starting currently.
 server-starting-cannot-stop = The server is currently starting. Please wait until the server is done with starting before you shut it down.
-stop-server = I stop the server.
+stop-server = I stop the server { $server }.
 server-stopped-externally = The server was stopped by another chat or an external system. I deactivate the chat bridge.
 server-running =
     { $currentPlayers ->
-        [0] The server is running. However, nobody is online right now.
-        *[other] The server is running and there are { $currentPlayers } of { $maxPlayers } players online: { $players }
+        [0] The server { $server } is running. However, nobody is online right now.
+        *[other] The server { $server } is running and there are { $currentPlayers } of { $maxPlayers } players online: { $players }
     }
+server-running-unknown-players = The server { $server } is running, but I could not read the list of players.
 chatbridge-activated = The chat bridge is already activated.
 chatbridge-activation-not-possible-server-not-running = The server is not running currently. Therefore, you cannot start the chat bridge.
 activate-chatbridge-after-start = Ok! When the server is done starting, I'll activate the chat bridge.
@@ -23,3 +26,87 @@ activate-chatbridge = I activate the chat bridge.
 chatbridge-deactivated = The chat bridge is already activated.
 deactivate-chatbridge = I deactivate the chat bridge.
 licence = This bot is free and libre software! The source code is licenced under the terms of the GPLv3 or any later version. The source code is available at https://github.com/PatrickJosh/minecraft-server-telegram-bot.
+player-joined = { $player } joined the game.
+player-left = { $player } left the game.
+player-advancement = { $player } has made the advancement [{ $advancement }].
+player-challenge = { $player } has completed the challenge [{ $advancement }].
+player-goal = { $player } has reached the goal [{ $advancement }].
+help = These are the commands I understand:
+unknown-command = I do not know the command /{ $command }.
+invalid-argument = I do not understand the argument “{ $argument }”.
+too-many-arguments = This command takes fewer arguments.
+command-start-server = Start the server
+command-stop-server = Stop the server
+command-status-server = Show whether the server is running and who is online
+command-enable-chatbridge = Mirror the chat between Minecraft and this chat
+command-disable-chatbridge = Stop mirroring the chat
+command-keep-alive = Keep the server running for the given number of hours, even if nobody is online
+command-schedule = Show when the scheduled actions run next
+command-licence = Show the licence of this bot
+command-help = Show the commands, or describe the given command
+permission-denied = You are not allowed to do this.
+bot-shutdown = I am going offline for now, the chat bridge is paused until I am back.
+media-photo = Photo
+media-sticker = Sticker
+media-sticker-emoji = Sticker { $emoji }
+media-sticker-set = From the sticker set { $set }
+media-voice = Voice message { $duration }
+media-audio = Audio
+media-audio-title = Audio: { $title }
+media-video = Video { $duration }
+media-video-note = Video message { $duration }
+media-animation = GIF
+media-document = File
+media-document-name = File: { $name }
+media-poll = Poll: { $question }
+media-venue = Location: { $title }
+media-location = Location
+media-contact = Contact: { $name }
+media-dice = Dice
+telegram-sender = { $name } on Telegram
+error-command = Something went wrong: I could not control the server. Please ask the administrator of the bot to look at its log.
+error-rcon = Something went wrong: I could not reach the server. Please try again later.
+error-message-too-long = This message is too long for Minecraft. Please send a shorter one.
+error-log = Something went wrong: I could not read the log of the server. Please ask the administrator of the bot to look into it.
+error-parse = Something went wrong: I did not understand the reply of the server.
+error-telegram = Something went wrong while talking to Telegram. Please try again later.
+error-config = Something went wrong: the configuration of the bot is invalid. Please ask the administrator of the bot to fix it.
+startup-loading-mods = ⚙️ Loading mods…
+startup-upgrading-world = 🔄 Upgrading the world…
+startup-preparing-level = 🌍 Preparing the world…
+startup-preparing-spawn = 🌍 Preparing the spawn area…
+startup-done = ✅ Done ({ $seconds }s)
+startup-ready = ✅ Ready
+startup-failed = ❌ Failed
+report-exceptions = Errors:
+report-log-tail = Last lines of the log:
+server-crashed = 💥 The server { $server } stopped unexpectedly ({ $reason }).
+watchdog-unreachable = no answer via RCON { $checks } times in a row
+crash-report = Crash report: { $file }
+watchdog-restarting = I restart the server in { $seconds } seconds.
+watchdog-restarted = The server is running again.
+watchdog-restart-limit = I already restarted the server { $restarts } times within the last hour, so I leave it stopped now. Please ask the administrator of the server to look into it.
+
+keep-alive = Ok! I won't stop the server within the next { $hours ->
+    [one] hour
+   *[other] { $hours } hours
+} just because nobody is online.
+keep-alive-ended = Ok! The server is stopped again once nobody has been online for a while.
+keep-alive-not-needed = The server is not stopped automatically anyway.
+idle-shutdown = I stopped the server { $server } because nobody was online for { $minutes } minutes.
+
+schedule-none = No actions are scheduled for this server.
+schedule-upcoming = Next scheduled actions ({ $timeZone }):
+schedule-action-start = Start the server
+schedule-action-stop = Stop the server
+schedule-action-restart = Restart the server
+schedule-action-broadcast = Broadcast “{ $message }”
+schedule-ran = Scheduled action for { $server }: { $action }
+schedule-server-running = The server is running.
+schedule-server-starting-slowly = The server is taking longer to start than expected.
+schedule-server-start-failed = The server failed to start: { $status }
+schedule-server-stopped = The server is stopped.
+schedule-server-stopping-slowly = The server is taking longer to stop than expected.
+schedule-skipped-not-running = Skipped, the server is not running.
+schedule-skipped-starting = Skipped, the server is just starting.
+schedule-skipped-running = Skipped, the server is already running.
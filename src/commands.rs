@@ -0,0 +1,344 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::config::Role;
+use std::str::{FromStr, SplitWhitespace};
+
+/// The commands the bot understands, without their arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    StartServer,
+    StopServer,
+    StatusServer,
+    EnableChatbridge,
+    DisableChatbridge,
+    KeepAlive,
+    Schedule,
+    Licence,
+    Help,
+}
+
+impl CommandKind {
+    pub const ALL: [CommandKind; 9] = [
+        CommandKind::StartServer,
+        CommandKind::StopServer,
+        CommandKind::StatusServer,
+        CommandKind::EnableChatbridge,
+        CommandKind::DisableChatbridge,
+        CommandKind::KeepAlive,
+        CommandKind::Schedule,
+        CommandKind::Licence,
+        CommandKind::Help,
+    ];
+
+    /// The command as typed by the user, without the leading slash.
+    pub fn name(self) -> &'static str {
+        match self {
+            CommandKind::StartServer => "start_server",
+            CommandKind::StopServer => "stop_server",
+            CommandKind::StatusServer => "status_server",
+            CommandKind::EnableChatbridge => "enable_chatbridge",
+            CommandKind::DisableChatbridge => "disable_chatbridge",
+            CommandKind::KeepAlive => "keep_alive",
+            CommandKind::Schedule => "schedule",
+            CommandKind::Licence => "licence",
+            CommandKind::Help => "help",
+        }
+    }
+
+    /// The role a user needs at least to use the command.
+    pub fn required_role(self) -> Role {
+        match self {
+            CommandKind::StatusServer
+            | CommandKind::Schedule
+            | CommandKind::Licence
+            | CommandKind::Help => Role::Viewer,
+            CommandKind::StartServer
+            | CommandKind::EnableChatbridge
+            | CommandKind::DisableChatbridge
+            | CommandKind::KeepAlive => Role::Member,
+            CommandKind::StopServer => Role::Operator,
+        }
+    }
+
+    /// Id of the localised description of the command.
+    pub fn description_id(self) -> &'static str {
+        match self {
+            CommandKind::StartServer => "command-start-server",
+            CommandKind::StopServer => "command-stop-server",
+            CommandKind::StatusServer => "command-status-server",
+            CommandKind::EnableChatbridge => "command-enable-chatbridge",
+            CommandKind::DisableChatbridge => "command-disable-chatbridge",
+            CommandKind::KeepAlive => "command-keep-alive",
+            CommandKind::Schedule => "command-schedule",
+            CommandKind::Licence => "command-licence",
+            CommandKind::Help => "command-help",
+        }
+    }
+}
+
+impl FromStr for CommandKind {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.strip_prefix('/').unwrap_or(name);
+        CommandKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or(())
+    }
+}
+
+/// A command including its parsed arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    StartServer,
+    StopServer,
+    StatusServer,
+    EnableChatbridge,
+    DisableChatbridge,
+    /// Suspends the idle shutdown for the given number of hours.
+    KeepAlive {
+        hours: Option<u32>,
+    },
+    Schedule,
+    Licence,
+    Help {
+        command: Option<CommandKind>,
+    },
+}
+
+impl Command {
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            Command::StartServer => CommandKind::StartServer,
+            Command::StopServer => CommandKind::StopServer,
+            Command::StatusServer => CommandKind::StatusServer,
+            Command::EnableChatbridge => CommandKind::EnableChatbridge,
+            Command::DisableChatbridge => CommandKind::DisableChatbridge,
+            Command::KeepAlive { .. } => CommandKind::KeepAlive,
+            Command::Schedule => CommandKind::Schedule,
+            Command::Licence => CommandKind::Licence,
+            Command::Help { .. } => CommandKind::Help,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Unknown(String),
+    InvalidArgument {
+        command: CommandKind,
+        argument: String,
+    },
+    TooManyArguments(CommandKind),
+}
+
+/// The arguments following a command, separated by whitespace.
+struct Arguments<'a> {
+    command: CommandKind,
+    arguments: SplitWhitespace<'a>,
+}
+
+impl Arguments<'_> {
+    fn optional<T: FromStr>(&mut self) -> Result<Option<T>, CommandError> {
+        match self.arguments.next() {
+            Some(argument) => {
+                argument
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| CommandError::InvalidArgument {
+                        command: self.command,
+                        argument: String::from(argument),
+                    })
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn finish(mut self) -> Result<(), CommandError> {
+        match self.arguments.next() {
+            Some(_) => Err(CommandError::TooManyArguments(self.command)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a message text as command.
+///
+/// Returns `None` if the text is no command or if the command is addressed to another bot via a
+/// `@botname` suffix.
+pub fn parse_command(text: &str, bot_username: &str) -> Option<Result<Command, CommandError>> {
+    let text = text.strip_prefix('/')?;
+    let mut arguments = text.split_whitespace();
+    let token = arguments.next()?;
+    let name = match token.split_once('@') {
+        Some((name, username)) => {
+            if !username.eq_ignore_ascii_case(bot_username) {
+                return None;
+            }
+            name
+        }
+        None => token,
+    };
+    let kind = match name.parse::<CommandKind>() {
+        Ok(kind) => kind,
+        Err(_) => return Some(Err(CommandError::Unknown(String::from(name)))),
+    };
+    let mut arguments = Arguments {
+        command: kind,
+        arguments,
+    };
+    Some(parse_arguments(kind, &mut arguments).and_then(|command| {
+        arguments.finish()?;
+        Ok(command)
+    }))
+}
+
+fn parse_arguments(kind: CommandKind, arguments: &mut Arguments) -> Result<Command, CommandError> {
+    Ok(match kind {
+        CommandKind::StartServer => Command::StartServer,
+        CommandKind::StopServer => Command::StopServer,
+        CommandKind::StatusServer => Command::StatusServer,
+        CommandKind::EnableChatbridge => Command::EnableChatbridge,
+        CommandKind::DisableChatbridge => Command::DisableChatbridge,
+        CommandKind::KeepAlive => Command::KeepAlive {
+            hours: arguments.optional()?,
+        },
+        CommandKind::Schedule => Command::Schedule,
+        CommandKind::Licence => Command::Licence,
+        CommandKind::Help => Command::Help {
+            command: arguments.optional()?,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT: &str = "minecraft_bot";
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            parse_command("/start_server", BOT),
+            Some(Ok(Command::StartServer))
+        );
+        assert_eq!(
+            parse_command("/status_server@minecraft_bot", BOT),
+            Some(Ok(Command::StatusServer))
+        );
+        assert_eq!(
+            parse_command("/keep_alive 3", BOT),
+            Some(Ok(Command::KeepAlive { hours: Some(3) }))
+        );
+        assert_eq!(
+            parse_command("/keep_alive", BOT),
+            Some(Ok(Command::KeepAlive { hours: None }))
+        );
+    }
+
+    #[test]
+    fn no_command() {
+        assert_eq!(parse_command("start_server", BOT), None);
+        assert_eq!(parse_command("/", BOT), None);
+        assert_eq!(parse_command("hello /start_server", BOT), None);
+    }
+
+    #[test]
+    fn other_bot() {
+        assert_eq!(parse_command("/start_server@OtherBot", BOT), None);
+        assert_eq!(parse_command("/unknown@OtherBot", BOT), None);
+    }
+
+    #[test]
+    fn username_ignores_case() {
+        assert_eq!(
+            parse_command("/start_server@Minecraft_Bot", BOT),
+            Some(Ok(Command::StartServer))
+        );
+    }
+
+    #[test]
+    fn unknown_command() {
+        assert_eq!(
+            parse_command("/start_servers", BOT),
+            Some(Err(CommandError::Unknown(String::from("start_servers"))))
+        );
+        assert_eq!(
+            parse_command("/Start_Server", BOT),
+            Some(Err(CommandError::Unknown(String::from("Start_Server"))))
+        );
+    }
+
+    #[test]
+    fn invalid_argument() {
+        assert_eq!(
+            parse_command("/keep_alive x", BOT),
+            Some(Err(CommandError::InvalidArgument {
+                command: CommandKind::KeepAlive,
+                argument: String::from("x"),
+            }))
+        );
+        assert_eq!(
+            parse_command("/keep_alive -1", BOT),
+            Some(Err(CommandError::InvalidArgument {
+                command: CommandKind::KeepAlive,
+                argument: String::from("-1"),
+            }))
+        );
+        assert_eq!(
+            parse_command("/help start", BOT),
+            Some(Err(CommandError::InvalidArgument {
+                command: CommandKind::Help,
+                argument: String::from("start"),
+            }))
+        );
+    }
+
+    #[test]
+    fn too_many_arguments() {
+        assert_eq!(
+            parse_command("/stop_server now", BOT),
+            Some(Err(CommandError::TooManyArguments(CommandKind::StopServer)))
+        );
+        assert_eq!(
+            parse_command("/keep_alive 2 3", BOT),
+            Some(Err(CommandError::TooManyArguments(CommandKind::KeepAlive)))
+        );
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(
+            parse_command("/help", BOT),
+            Some(Ok(Command::Help { command: None }))
+        );
+        assert_eq!(
+            parse_command("/help /start_server", BOT),
+            Some(Ok(Command::Help {
+                command: Some(CommandKind::StartServer)
+            }))
+        );
+        assert_eq!(
+            parse_command("/help keep_alive", BOT),
+            Some(Ok(Command::Help {
+                command: Some(CommandKind::KeepAlive)
+            }))
+        );
+    }
+}
@@ -0,0 +1,561 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::BotError;
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    pub token: String,
+    /// RCON password of the old configuration format, used for all servers that have no entry
+    /// in `servers`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rcon_password: Option<String>,
+    pub locale: String,
+    #[serde(default)]
+    pub servers: HashMap<String, ServerConfig>,
+    /// Maps chat ids to the keys of `servers`.
+    pub chat_server_map: HashMap<String, String>,
+    /// Optional settings per chat id, chats without an entry use the defaults.
+    #[serde(default)]
+    pub chat_settings: HashMap<String, ChatSettings>,
+    /// Maps Telegram user ids to their role.
+    #[serde(default)]
+    pub user_roles: HashMap<String, Role>,
+    /// Role of users without an entry in `user_roles`.
+    #[serde(default = "default_role")]
+    pub default_role: Role,
+    /// Role the administrators of a chat have at least within that chat.
+    #[serde(default = "default_chat_admin_role")]
+    pub chat_admin_role: Role,
+    /// Messages older than this many seconds are dropped instead of handled, e.g. after the bot
+    /// was offline for a while.
+    #[serde(default = "default_max_update_age")]
+    pub max_update_age: u64,
+    /// How many seconds a request for updates waits for new updates (long polling).
+    #[serde(default = "default_polling_timeout")]
+    pub polling_timeout: u32,
+    /// Receive updates via a webhook instead of polling for them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
+    /// Time zone the schedules of the servers are in, e.g. `Europe/Berlin`.
+    #[serde(default = "default_time_zone")]
+    pub time_zone: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    /// The public URL Telegram sends the updates to, e.g. the one of a reverse proxy.
+    pub url: String,
+    /// The address the HTTP server of the bot listens on.
+    #[serde(default = "default_webhook_listen")]
+    pub listen: SocketAddr,
+    /// Sent by Telegram along with every update. Requests without it are rejected.
+    pub secret_token: String,
+}
+
+/// What a user may do. Every role may do everything the roles before it may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// May only look at the status of the server.
+    Viewer,
+    /// May additionally start the server and use the chat bridge.
+    Member,
+    /// May additionally stop the server.
+    Operator,
+    Admin,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatSettings {
+    /// Whether the chat bridge relays players joining and leaving the server.
+    #[serde(default = "default_true")]
+    pub join_leave_messages: bool,
+    /// Whether join and leave messages are sent without notification.
+    #[serde(default)]
+    pub join_leave_silent: bool,
+    /// Whether the chat is told when the bot shuts down while the chat bridge is active.
+    #[serde(default = "default_true")]
+    pub shutdown_message: bool,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        ChatSettings {
+            join_leave_messages: true,
+            join_leave_silent: false,
+            shutdown_message: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default = "default_rcon_host")]
+    pub rcon_host: String,
+    #[serde(default = "default_rcon_port")]
+    pub rcon_port: u16,
+    pub rcon_password: String,
+    /// Name of the systemd unit, `{}` is replaced by the key of the server.
+    #[serde(default = "default_service_unit")]
+    pub service_unit: String,
+    #[serde(default)]
+    pub service_manager: ServiceManagerConfig,
+    #[serde(default)]
+    pub log_source: LogSourceConfig,
+    /// Seconds after which the chat is told that the start takes longer. The start is still
+    /// watched afterwards.
+    #[serde(default = "default_start_timeout")]
+    pub start_timeout: u64,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    /// Minutes without players after which the server is stopped. Disabled if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_shutdown: Option<u64>,
+    /// Actions run at fixed times.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<ScheduleConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScheduleConfig {
+    /// When the action is run as cron expression, e.g. `0 4 * * *` for 04:00 every day. A leading
+    /// field for the seconds may be given as well.
+    pub cron: String,
+    #[serde(flatten)]
+    pub action: ScheduledAction,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ScheduledAction {
+    Start,
+    Stop,
+    Restart,
+    /// Show a message to all players on the server.
+    Broadcast {
+        message: String,
+    },
+}
+
+/// Detection of crashes of a running server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchdogConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Seconds between two checks of the server.
+    #[serde(default = "default_watchdog_interval")]
+    pub check_interval: u64,
+    /// Number of failed RCON requests in a row after which a running server counts as crashed.
+    #[serde(default = "default_rcon_failures")]
+    pub rcon_failures: u32,
+    /// Whether a crashed server is started again.
+    #[serde(default)]
+    pub restart: bool,
+    /// Once the server was restarted this often within an hour, it is left stopped.
+    #[serde(default = "default_max_restarts_per_hour")]
+    pub max_restarts_per_hour: u32,
+    /// Directory the server writes its crash reports to. Defaults to `crash-reports` in the
+    /// server directory, if it is known from `service_manager` or `log_source`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crash_reports: Option<PathBuf>,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            enabled: true,
+            check_interval: default_watchdog_interval(),
+            rcon_failures: default_rcon_failures(),
+            restart: false,
+            max_restarts_per_hour: default_max_restarts_per_hour(),
+            crash_reports: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServiceManagerConfig {
+    /// Control `service_unit` via `sudo systemctl`.
+    #[default]
+    Systemd,
+    /// Control a Docker container via `docker start|stop|inspect`.
+    Docker { container: String },
+    /// Run the server as a child process of the bot.
+    Process {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        working_directory: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogSourceConfig {
+    /// Follow the journal of `unit`, by default the systemd unit of the server.
+    Journald {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit: Option<String>,
+    },
+    /// Follow a log file of the server, usually `logs/latest.log` in the server directory.
+    File { path: PathBuf },
+}
+
+impl Default for LogSourceConfig {
+    fn default() -> Self {
+        LogSourceConfig::Journald { unit: None }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_time_zone() -> String {
+    String::from("UTC")
+}
+
+fn default_role() -> Role {
+    Role::Member
+}
+
+fn default_chat_admin_role() -> Role {
+    Role::Operator
+}
+
+fn default_max_update_age() -> u64 {
+    300
+}
+
+fn default_polling_timeout() -> u32 {
+    30
+}
+
+fn default_webhook_listen() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8080))
+}
+
+fn default_rcon_host() -> String {
+    String::from("localhost")
+}
+
+fn default_rcon_port() -> u16 {
+    25575
+}
+
+fn default_service_unit() -> String {
+    String::from("minecraft-server@{}.service")
+}
+
+fn default_start_timeout() -> u64 {
+    60
+}
+
+fn default_watchdog_interval() -> u64 {
+    30
+}
+
+fn default_rcon_failures() -> u32 {
+    3
+}
+
+fn default_max_restarts_per_hour() -> u32 {
+    3
+}
+
+impl Config {
+    pub fn chat_settings(&self, chat_id: i64) -> ChatSettings {
+        self.chat_settings
+            .get(&chat_id.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn load(path: &str) -> Result<Config, BotError> {
+        let config_file = fs::read_to_string(path)
+            .map_err(|err| BotError::Config(format!("could not read {}: {}", path, err)))?;
+        let mut config: Config = serde_json::from_str(&config_file)
+            .map_err(|err| BotError::Config(format!("could not parse {}: {}", path, err)))?;
+        config.add_legacy_servers()?;
+        config.check_schedules()?;
+        config.check_watchdogs()?;
+        Ok(config)
+    }
+
+    pub fn time_zone(&self) -> Result<Tz, BotError> {
+        self.time_zone.parse().map_err(|err| {
+            BotError::Config(format!("invalid time zone {}: {}", self.time_zone, err))
+        })
+    }
+
+    /// Makes sure that the time zone and all schedules can be parsed, so that mistakes show up
+    /// at startup and not only when the schedules are run.
+    fn check_schedules(&self) -> Result<(), BotError> {
+        self.time_zone()?;
+        for server in self.servers.values() {
+            for schedule in &server.schedules {
+                schedule.schedule()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes sure that the watchdogs check the servers at all, a check interval of 0 is invalid.
+    fn check_watchdogs(&self) -> Result<(), BotError> {
+        for (server_name, server) in &self.servers {
+            if server.watchdog.check_interval == 0 {
+                return Err(BotError::Config(format!(
+                    "check_interval of the watchdog of server {} must be at least 1",
+                    server_name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a server entry with the old defaults for every server that is only referenced in
+    /// `chat_server_map`, so that configurations without `servers` keep working.
+    fn add_legacy_servers(&mut self) -> Result<(), BotError> {
+        for server_name in self.chat_server_map.values() {
+            if !self.servers.contains_key(server_name) {
+                let rcon_password = self.rcon_password.clone().ok_or_else(|| {
+                    BotError::Config(format!(
+                        "server {} is neither configured in servers nor is rcon_password set",
+                        server_name
+                    ))
+                })?;
+                self.servers.insert(
+                    server_name.clone(),
+                    ServerConfig {
+                        display_name: None,
+                        rcon_host: default_rcon_host(),
+                        rcon_port: default_rcon_port(),
+                        rcon_password,
+                        service_unit: default_service_unit(),
+                        service_manager: ServiceManagerConfig::default(),
+                        log_source: LogSourceConfig::default(),
+                        start_timeout: default_start_timeout(),
+                        watchdog: WatchdogConfig::default(),
+                        idle_shutdown: None,
+                        schedules: Vec::new(),
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ScheduleConfig {
+    pub fn schedule(&self) -> Result<Schedule, BotError> {
+        let fields = self.cron.split_whitespace().collect::<Vec<&str>>();
+        let cron = match fields.as_slice() {
+            // The cron crate expects the seconds as well and counts the days of the week from 1
+            // for Sunday, unlike crontab.
+            [minutes, hours, days, months, days_of_week] => format!(
+                "0 {} {} {} {} {}",
+                minutes,
+                hours,
+                days,
+                months,
+                crontab_days_of_week(days_of_week)
+            ),
+            _ => self.cron.clone(),
+        };
+        Schedule::from_str(&cron)
+            .map_err(|err| BotError::Config(format!("invalid schedule {}: {}", self.cron, err)))
+    }
+}
+
+/// Names of the days of the week as counted by crontab, where both 0 and 7 are Sunday.
+const DAY_NAMES: [&str; 8] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Replaces the numbers in the day of the week field of a crontab expression with the names of
+/// the days, e.g. `1-5` with `Mon-Fri`. Anything else is left for the cron crate to check.
+fn crontab_days_of_week(field: &str) -> String {
+    let day_name = |day: &str| {
+        day.parse::<usize>()
+            .ok()
+            .and_then(|day| DAY_NAMES.get(day).copied())
+            .unwrap_or(day)
+            .to_string()
+    };
+    field
+        .split(',')
+        .map(|item| {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+            let range = match range.split_once('-') {
+                // Sunday comes first for the cron crate, so a range cannot end with it.
+                Some((start, "7")) if step.is_none() => format!("{}-Sat,Sun", day_name(start)),
+                Some((start, end)) => format!("{}-{}", day_name(start), day_name(end)),
+                None => day_name(range),
+            };
+            match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+impl ServerConfig {
+    /// The name of the server shown in messages.
+    pub fn name<'a>(&'a self, server_name: &'a str) -> &'a str {
+        self.display_name.as_deref().unwrap_or(server_name)
+    }
+
+    pub fn rcon_address(&self) -> String {
+        format!("{}:{}", self.rcon_host, self.rcon_port)
+    }
+
+    pub fn service_name(&self, server_name: &str) -> String {
+        self.service_unit.replace("{}", server_name)
+    }
+
+    /// The directory of the crash reports, see [`WatchdogConfig::crash_reports`].
+    pub fn crash_reports(&self) -> Option<PathBuf> {
+        if let Some(crash_reports) = &self.watchdog.crash_reports {
+            return Some(crash_reports.clone());
+        }
+        let server_directory = match (&self.service_manager, &self.log_source) {
+            (
+                ServiceManagerConfig::Process {
+                    working_directory: Some(working_directory),
+                    ..
+                },
+                _,
+            ) => working_directory.clone(),
+            // The log is usually `logs/latest.log` in the server directory.
+            (_, LogSourceConfig::File { path }) => path.parent()?.parent()?.to_path_buf(),
+            _ => return None,
+        };
+        Some(server_directory.join("crash-reports"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Timelike, Weekday};
+
+    fn schedule(cron: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            cron: String::from(cron),
+            action: ScheduledAction::Start,
+        }
+    }
+
+    #[test]
+    fn crontab_days_of_week() {
+        assert_eq!(super::crontab_days_of_week("*"), "*");
+        assert_eq!(super::crontab_days_of_week("0"), "Sun");
+        assert_eq!(super::crontab_days_of_week("7"), "Sun");
+        assert_eq!(super::crontab_days_of_week("5"), "Fri");
+        assert_eq!(super::crontab_days_of_week("1-5"), "Mon-Fri");
+        assert_eq!(super::crontab_days_of_week("5-7"), "Fri-Sat,Sun");
+        assert_eq!(super::crontab_days_of_week("0,6"), "Sun,Sat");
+        assert_eq!(super::crontab_days_of_week("1-5/2"), "Mon-Fri/2");
+        assert_eq!(super::crontab_days_of_week("Mon-Fri"), "Mon-Fri");
+    }
+
+    #[test]
+    fn numeric_days_of_week() {
+        let time_zone: Tz = "Europe/Berlin".parse().unwrap();
+        for (cron, weekday) in [
+            ("0 18 * * 5", Weekday::Fri),
+            ("0 18 * * Fri", Weekday::Fri),
+            ("0 0 * * 0", Weekday::Sun),
+            ("0 0 * * 7", Weekday::Sun),
+        ] {
+            let next = schedule(cron)
+                .schedule()
+                .unwrap()
+                .upcoming(time_zone)
+                .next()
+                .unwrap();
+            assert_eq!(next.weekday(), weekday, "{}", cron);
+        }
+    }
+
+    #[test]
+    fn weekend_range() {
+        let days = schedule("0 12 * * 5-7")
+            .schedule()
+            .unwrap()
+            .upcoming(chrono::Utc)
+            .take(7)
+            .map(|time| time.weekday())
+            .collect::<Vec<Weekday>>();
+        assert_eq!(days.len(), 7);
+        assert!(days
+            .iter()
+            .all(|day| matches!(day, Weekday::Fri | Weekday::Sat | Weekday::Sun)));
+    }
+
+    #[test]
+    fn seconds_field() {
+        let next = schedule("30 0 4 * * *")
+            .schedule()
+            .unwrap()
+            .upcoming(chrono::Utc)
+            .next()
+            .unwrap();
+        assert_eq!((next.hour(), next.minute(), next.second()), (4, 0, 30));
+    }
+
+    #[test]
+    fn watchdog_check_interval() {
+        let config = |check_interval: u64| -> Config {
+            serde_json::from_value(serde_json::json!({
+                "token": "token",
+                "locale": "en-UK",
+                "servers": {
+                    "survival": {
+                        "rcon_password": "secret",
+                        "watchdog": { "check_interval": check_interval },
+                    },
+                },
+                "chat_server_map": {},
+            }))
+            .unwrap()
+        };
+        assert!(config(30).check_watchdogs().is_ok());
+        assert!(config(0).check_watchdogs().is_err());
+    }
+
+    #[test]
+    fn invalid_schedule() {
+        assert!(schedule("0 18 * * 8").schedule().is_err());
+        assert!(schedule("every friday").schedule().is_err());
+    }
+}
@@ -0,0 +1,123 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::rcon::RconError;
+use std::fmt;
+use std::io;
+
+/// Errors passed up by the handlers, so that they can be reported in the chat.
+#[derive(Debug)]
+pub enum BotError {
+    /// A command controlling the server, e.g. `systemctl`, could not be run or failed.
+    Command(io::Error),
+    Rcon(RconError),
+    /// The log of the server could not be followed.
+    Log(io::Error),
+    /// The server replied with something unexpected.
+    Parse(String),
+    Telegram(frankenstein::Error),
+    Config(String),
+}
+
+impl BotError {
+    /// Short name of the kind of error for the log.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BotError::Command(_) => "command",
+            BotError::Rcon(_) => "rcon",
+            BotError::Log(_) => "log",
+            BotError::Parse(_) => "parse",
+            BotError::Telegram(_) => "telegram",
+            BotError::Config(_) => "config",
+        }
+    }
+
+    /// The id of the localised message shown to the user.
+    pub fn text_id(&self) -> &'static str {
+        match self {
+            BotError::Command(_) => "error-command",
+            // Sending the same message again would fail again.
+            BotError::Rcon(RconError::CommandTooLong) => "error-message-too-long",
+            BotError::Rcon(_) => "error-rcon",
+            BotError::Log(_) => "error-log",
+            BotError::Parse(_) => "error-parse",
+            BotError::Telegram(_) => "error-telegram",
+            BotError::Config(_) => "error-config",
+        }
+    }
+
+    /// Logs the error as a single line of `key=value` pairs. `context` names the handler or task
+    /// the error occurred in.
+    pub fn log(&self, context: &str, chat_id: Option<i64>) {
+        let chat = chat_id
+            .map(|chat_id| format!(" chat={}", chat_id))
+            .unwrap_or_default();
+        println!(
+            "error kind={} context={}{} message={:?}",
+            self.kind(),
+            context,
+            chat,
+            self.to_string()
+        );
+    }
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Command(err) => write!(f, "command failed: {}", err),
+            BotError::Rcon(err) => write!(f, "RCON failed: {}", err),
+            BotError::Log(err) => write!(f, "could not follow log: {}", err),
+            BotError::Parse(message) => write!(f, "could not parse: {}", message),
+            BotError::Telegram(err) => write!(f, "Telegram request failed: {:?}", err),
+            BotError::Config(message) => write!(f, "invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+impl From<io::Error> for BotError {
+    fn from(err: io::Error) -> Self {
+        BotError::Command(err)
+    }
+}
+
+impl From<RconError> for BotError {
+    fn from(err: RconError) -> Self {
+        BotError::Rcon(err)
+    }
+}
+
+impl From<frankenstein::Error> for BotError {
+    fn from(err: frankenstein::Error) -> Self {
+        BotError::Telegram(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_too_long() {
+        assert_eq!(
+            BotError::from(RconError::CommandTooLong).text_id(),
+            "error-message-too-long"
+        );
+        assert_eq!(BotError::from(RconError::Timeout).text_id(), "error-rcon");
+    }
+}
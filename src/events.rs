@@ -0,0 +1,626 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Parses lines of the server log into [`ServerEvent`]s.
+//!
+//! The parser understands the log formats of Vanilla, Paper, Spigot, Forge and Fabric, both as
+//! written to `logs/latest.log` and as printed to the console (and thereby to the journal).
+
+use regex::Regex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// `<player> message`
+    Chat {
+        player: String,
+        message: String,
+    },
+    /// `* player action`, sent with `/me`.
+    Emote {
+        player: String,
+        action: String,
+    },
+    /// `[sender] message`, sent with `/say` from the console (`Server`), via RCON (`Rcon`) or by a
+    /// command block (`@`). The `/say` of players is not recognised, since it looks like the lines
+    /// plugins log with their name in brackets, e.g. `[LuckPerms] Performing sync…`.
+    Say {
+        sender: String,
+        message: String,
+    },
+    Join {
+        player: String,
+    },
+    Leave {
+        player: String,
+    },
+    /// `message` is the complete death message, including the name of the player.
+    Death {
+        player: String,
+        message: String,
+    },
+    Advancement {
+        player: String,
+        kind: AdvancementKind,
+        title: String,
+    },
+    /// The server reached a further phase of its start.
+    Progress(StartupPhase),
+    /// The server is done starting and took the given time for it.
+    Done(Duration),
+    Stopping,
+    Exception(String),
+    CantKeepUp {
+        behind: Duration,
+        ticks: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvancementKind {
+    Advancement,
+    Challenge,
+    Goal,
+}
+
+/// The phases of the start of a server, in the order they usually occur in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupPhase {
+    /// Forge, Fabric or Quilt load the mods.
+    LoadingMods,
+    /// The world is upgraded to the current version, with the percentage done if known.
+    UpgradingWorld(Option<u8>),
+    PreparingLevel,
+    /// The chunks around the spawn are generated, with the percentage done.
+    PreparingSpawn(u8),
+}
+
+/// Beginnings of the vanilla death messages, following the name of the player.
+const DEATH_MESSAGE_PREFIXES: &[&str] = &[
+    "was shot",
+    "was pummeled",
+    "was pricked to death",
+    "was killed",
+    "was blown up",
+    "was blown from a high place",
+    "was slain by",
+    "was fireballed by",
+    "was stung to death",
+    "was squashed",
+    "was squished too much",
+    "was impaled",
+    "was skewered by a falling stalactite",
+    "was struck by lightning",
+    "was burnt to a crisp",
+    "was burned to a crisp",
+    "was frozen to death",
+    "was poked to death",
+    "was roasted in dragon breath",
+    "was obliterated by",
+    "was doomed to fall",
+    "was spitballed by",
+    "was smashed by",
+    "walked into",
+    "drowned",
+    "died",
+    "experienced kinetic energy",
+    "blew up",
+    "hit the ground too hard",
+    "fell",
+    "went up in flames",
+    "went off with a bang",
+    "burned to death",
+    "tried to swim in lava",
+    "discovered the floor was lava",
+    "froze to death",
+    "starved to death",
+    "suffocated in a wall",
+    "didn't want to live in the same world as",
+    "withered away",
+    "left the confines of this world",
+];
+
+#[derive(Debug, Clone)]
+pub struct EventParser {
+    header_regex: Regex,
+    chat_regex: Regex,
+    emote_regex: Regex,
+    say_regex: Regex,
+    join_regex: Regex,
+    leave_regex: Regex,
+    death_regex: Regex,
+    advancement_regex: Regex,
+    done_regex: Regex,
+    loading_mods_regex: Regex,
+    upgrading_world_regex: Regex,
+    upgrade_progress_regex: Regex,
+    preparing_level_regex: Regex,
+    preparing_spawn_regex: Regex,
+    stopping_regex: Regex,
+    cant_keep_up_regex: Regex,
+    stack_trace_regex: Regex,
+}
+
+impl Default for EventParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventParser {
+    pub fn new() -> Self {
+        let death_prefixes = DEATH_MESSAGE_PREFIXES
+            .iter()
+            .map(|prefix| regex::escape(prefix))
+            .collect::<Vec<String>>()
+            .join("|");
+        EventParser {
+            // Matches the part after the time and the log level, e.g. `[12:00:00] [Server thread/INFO]: `
+            // (Vanilla), `[12:00:00 INFO]: ` (Paper, Spigot),
+            // `[12:00:00] [Server thread/INFO] [minecraft/DedicatedServer]: ` (Forge) or
+            // `[12:00:00] [Server thread/INFO] (Minecraft) ` (Fabric). Anything before, like the
+            // prefix added by the journal, is skipped.
+            header_regex: Regex::new(
+                r"\[[^\]]*?[/ ](INFO|WARN|WARNING|ERROR|SEVERE|FATAL)\](?: \[[^\]]*\])?(?::| \([^)]*\)) (.*)$",
+            )
+            .unwrap(),
+            chat_regex: Regex::new(r"^(?:\[Not Secure\] )?<([^>\s]+)> (.*)$").unwrap(),
+            emote_regex: Regex::new(r"^(?:\[Not Secure\] )?\* (\S+) (.*)$").unwrap(),
+            say_regex: Regex::new(r"^(?:\[Not Secure\] )?\[(Server|Rcon|@)\] (.*)$").unwrap(),
+            join_regex: Regex::new(r"^(\S+)(?: \(formerly known as \S+\))? joined the game$")
+                .unwrap(),
+            leave_regex: Regex::new(r"^(\S+) left the game$").unwrap(),
+            death_regex: Regex::new(&format!(r"^(\S+) (?:{})(?:\W.*)?$", death_prefixes)).unwrap(),
+            advancement_regex: Regex::new(
+                r"^(\S+) has (made the advancement|completed the challenge|reached the goal) \[(.+)\]$",
+            )
+            .unwrap(),
+            done_regex: Regex::new(r"^Done \(([0-9]+(?:[.,][0-9]+)?)s\)!").unwrap(),
+            loading_mods_regex: Regex::new(
+                r"^(?:Loading [0-9]+ mods:|ModLauncher running|Forge mod loading|Loading Minecraft \S+ with (?:Fabric|Quilt) Loader)",
+            )
+            .unwrap(),
+            upgrading_world_regex: Regex::new(
+                r"^(?:Forcing world upgrade|Upgrading all chunks for dimension|Converting map)",
+            )
+            .unwrap(),
+            upgrade_progress_regex: Regex::new(
+                r"^\S+ ([0-9]+)% completed \([0-9]+ / [0-9]+ chunks\)",
+            )
+            .unwrap(),
+            preparing_level_regex: Regex::new(r#"^Preparing level "[^"]*""#).unwrap(),
+            // `Preparing start region` precedes the percentages, older versions only print it
+            // without any percentage.
+            preparing_spawn_regex: Regex::new(
+                r"^(?:Preparing spawn area: ([0-9]+)%|Preparing start region)",
+            )
+            .unwrap(),
+            stopping_regex: Regex::new(r"^Stopping (?:the )?server$").unwrap(),
+            cant_keep_up_regex: Regex::new(
+                r"^Can't keep up! Is the server overloaded\? Running ([0-9]+)ms or ([0-9]+) ticks behind",
+            )
+            .unwrap(),
+            // Stack traces are printed without a header, e.g. `java.lang.IllegalStateException: …`.
+            stack_trace_regex: Regex::new(
+                r"(?:^|: )((?:Caused by: )?(?:[a-z_$][\w$]*\.)+[A-Z][\w$]*(?:Exception|Error)(?::.*)?)$",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Parses a line of the log. Returns `None` for lines that are not of interest.
+    pub fn parse(&self, line: &str) -> Option<ServerEvent> {
+        let captures = match self.header_regex.captures(line) {
+            Some(captures) => captures,
+            None => {
+                return self
+                    .stack_trace_regex
+                    .captures(line)
+                    .map(|captures| ServerEvent::Exception(String::from(&captures[1])));
+            }
+        };
+        let level = &captures[1];
+        let text = captures[2].trim_end();
+
+        if matches!(level, "ERROR" | "SEVERE" | "FATAL") {
+            return if text.contains("xception") || text.contains("rror") {
+                Some(ServerEvent::Exception(String::from(text)))
+            } else {
+                None
+            };
+        }
+
+        if let Some(captures) = self.chat_regex.captures(text) {
+            Some(ServerEvent::Chat {
+                player: String::from(&captures[1]),
+                message: String::from(&captures[2]),
+            })
+        } else if let Some(captures) = self.emote_regex.captures(text) {
+            Some(ServerEvent::Emote {
+                player: String::from(&captures[1]),
+                action: String::from(&captures[2]),
+            })
+        } else if let Some(captures) = self.say_regex.captures(text) {
+            Some(ServerEvent::Say {
+                sender: String::from(&captures[1]),
+                message: String::from(&captures[2]),
+            })
+        } else if let Some(captures) = self.join_regex.captures(text) {
+            Some(ServerEvent::Join {
+                player: String::from(&captures[1]),
+            })
+        } else if let Some(captures) = self.leave_regex.captures(text) {
+            Some(ServerEvent::Leave {
+                player: String::from(&captures[1]),
+            })
+        } else if let Some(captures) = self.advancement_regex.captures(text) {
+            let kind = match &captures[2] {
+                "completed the challenge" => AdvancementKind::Challenge,
+                "reached the goal" => AdvancementKind::Goal,
+                _ => AdvancementKind::Advancement,
+            };
+            Some(ServerEvent::Advancement {
+                player: String::from(&captures[1]),
+                kind,
+                title: String::from(&captures[3]),
+            })
+        } else if let Some(captures) = self.done_regex.captures(text) {
+            let seconds = captures[1].replace(',', ".").parse().unwrap_or(0.0);
+            Some(ServerEvent::Done(Duration::from_secs_f64(seconds)))
+        } else if let Some(phase) = self.parse_startup_phase(text) {
+            Some(ServerEvent::Progress(phase))
+        } else if self.stopping_regex.is_match(text) {
+            Some(ServerEvent::Stopping)
+        } else if let Some(captures) = self.cant_keep_up_regex.captures(text) {
+            Some(ServerEvent::CantKeepUp {
+                behind: Duration::from_millis(captures[1].parse().unwrap_or(0)),
+                ticks: captures[2].parse().unwrap_or(0),
+            })
+        } else {
+            self.death_regex
+                .captures(text)
+                .map(|captures| ServerEvent::Death {
+                    player: String::from(&captures[1]),
+                    message: String::from(text),
+                })
+        }
+    }
+
+    fn parse_startup_phase(&self, text: &str) -> Option<StartupPhase> {
+        if self.loading_mods_regex.is_match(text) {
+            Some(StartupPhase::LoadingMods)
+        } else if let Some(captures) = self.upgrade_progress_regex.captures(text) {
+            Some(StartupPhase::UpgradingWorld(captures[1].parse().ok()))
+        } else if self.upgrading_world_regex.is_match(text) {
+            Some(StartupPhase::UpgradingWorld(None))
+        } else if self.preparing_level_regex.is_match(text) {
+            Some(StartupPhase::PreparingLevel)
+        } else {
+            self.preparing_spawn_regex.captures(text).map(|captures| {
+                StartupPhase::PreparingSpawn(
+                    captures
+                        .get(1)
+                        .and_then(|percent| percent.as_str().parse().ok())
+                        .unwrap_or(0),
+                )
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<ServerEvent> {
+        EventParser::new().parse(line)
+    }
+
+    fn chat(player: &str, message: &str) -> Option<ServerEvent> {
+        Some(ServerEvent::Chat {
+            player: String::from(player),
+            message: String::from(message),
+        })
+    }
+
+    fn say(sender: &str, message: &str) -> Option<ServerEvent> {
+        Some(ServerEvent::Say {
+            sender: String::from(sender),
+            message: String::from(message),
+        })
+    }
+
+    fn join(player: &str) -> Option<ServerEvent> {
+        Some(ServerEvent::Join {
+            player: String::from(player),
+        })
+    }
+
+    #[test]
+    fn vanilla() {
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: <Steve> Hello there"),
+            chat("Steve", "Hello there")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: [Not Secure] <Steve> Hello"),
+            chat("Steve", "Hello")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: * Steve waves"),
+            Some(ServerEvent::Emote {
+                player: String::from("Steve"),
+                action: String::from("waves"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: [Server] Restart in 5 minutes"),
+            say("Server", "Restart in 5 minutes")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: [Rcon] Backup done"),
+            say("Rcon", "Backup done")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve joined the game"),
+            join("Steve")
+        );
+        assert_eq!(
+            parse(
+                "[12:00:00] [Server thread/INFO]: Steve (formerly known as Alex) joined the game"
+            ),
+            join("Steve")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve left the game"),
+            Some(ServerEvent::Leave {
+                player: String::from("Steve"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve was slain by Zombie"),
+            Some(ServerEvent::Death {
+                player: String::from("Steve"),
+                message: String::from("Steve was slain by Zombie"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve drowned"),
+            Some(ServerEvent::Death {
+                player: String::from("Steve"),
+                message: String::from("Steve drowned"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve has made the advancement [Stone Age]"),
+            Some(ServerEvent::Advancement {
+                player: String::from("Steve"),
+                kind: AdvancementKind::Advancement,
+                title: String::from("Stone Age"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve has completed the challenge [Monsters Hunted]"),
+            Some(ServerEvent::Advancement {
+                player: String::from("Steve"),
+                kind: AdvancementKind::Challenge,
+                title: String::from("Monsters Hunted"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Steve has reached the goal [Sky's the Limit]"),
+            Some(ServerEvent::Advancement {
+                player: String::from("Steve"),
+                kind: AdvancementKind::Goal,
+                title: String::from("Sky's the Limit"),
+            })
+        );
+        assert_eq!(
+            parse(r#"[12:00:00] [Server thread/INFO]: Done (12.345s)! For help, type "help""#),
+            Some(ServerEvent::Done(Duration::from_millis(12345)))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Stopping server"),
+            Some(ServerEvent::Stopping)
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running 2500ms or 50 ticks behind"),
+            Some(ServerEvent::CantKeepUp {
+                behind: Duration::from_millis(2500),
+                ticks: 50,
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/ERROR]: Encountered an unexpected exception"),
+            Some(ServerEvent::Exception(String::from(
+                "Encountered an unexpected exception"
+            )))
+        );
+        assert_eq!(
+            parse("java.lang.IllegalStateException: Duplicate key"),
+            Some(ServerEvent::Exception(String::from(
+                "java.lang.IllegalStateException: Duplicate key"
+            )))
+        );
+        assert_eq!(
+            parse("Caused by: java.lang.NullPointerException"),
+            Some(ServerEvent::Exception(String::from(
+                "Caused by: java.lang.NullPointerException"
+            )))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Starting minecraft server version 1.20.1"),
+            None
+        );
+    }
+
+    #[test]
+    fn vanilla_startup_phases() {
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Forcing world upgrade!"),
+            Some(ServerEvent::Progress(StartupPhase::UpgradingWorld(None)))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: minecraft:overworld 42% completed (420 / 1000 chunks)..."),
+            Some(ServerEvent::Progress(StartupPhase::UpgradingWorld(Some(42))))
+        );
+        assert_eq!(
+            parse(r#"[12:00:00] [Server thread/INFO]: Preparing level "world""#),
+            Some(ServerEvent::Progress(StartupPhase::PreparingLevel))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: Preparing start region for dimension minecraft:overworld"),
+            Some(ServerEvent::Progress(StartupPhase::PreparingSpawn(0)))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Worker-Main-2/INFO]: Preparing spawn area: 42%"),
+            Some(ServerEvent::Progress(StartupPhase::PreparingSpawn(42)))
+        );
+    }
+
+    #[test]
+    fn journald_prefix() {
+        assert_eq!(
+            parse("Mar 01 12:00:00 host java[1234]: [12:00:00] [Server thread/INFO]: <Steve> Hi"),
+            chat("Steve", "Hi")
+        );
+        assert_eq!(
+            parse("Mar 01 12:00:00 host java[1234]: [12:00:00 INFO]: Steve joined the game"),
+            join("Steve")
+        );
+        assert_eq!(
+            parse("Mar 01 12:00:00 host java[1234]: [12:00:00 INFO]: [LuckPerms] Performing sync…"),
+            None
+        );
+    }
+
+    #[test]
+    fn paper() {
+        assert_eq!(
+            parse("[12:00:00 INFO]: <Steve> Hello"),
+            chat("Steve", "Hello")
+        );
+        assert_eq!(
+            parse("[12:00:00 INFO]: Steve joined the game"),
+            join("Steve")
+        );
+        assert_eq!(
+            parse("[12:00:00 INFO]: [Server] Restart in 5 minutes"),
+            say("Server", "Restart in 5 minutes")
+        );
+        // Plugins log with their name in brackets, both on the console and in `latest.log`.
+        assert_eq!(parse("[12:00:00 INFO]: [LuckPerms] Performing sync…"), None);
+        assert_eq!(
+            parse("[12:00:00 INFO]: [Essentials] Enabling Essentials v2.20.1"),
+            None
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO]: [LuckPerms] Loading configuration..."),
+            None
+        );
+        assert_eq!(
+            parse(r#"[12:00:00 INFO]: Done (3.456s)! For help, type "help""#),
+            Some(ServerEvent::Done(Duration::from_millis(3456)))
+        );
+        assert_eq!(
+            parse("[12:00:00 WARN]: Can't keep up! Is the server overloaded? Running 5000ms or 100 ticks behind"),
+            Some(ServerEvent::CantKeepUp {
+                behind: Duration::from_millis(5000),
+                ticks: 100,
+            })
+        );
+    }
+
+    #[test]
+    fn spigot() {
+        assert_eq!(
+            parse("[12:00:00 INFO]: <Steve> Hello"),
+            chat("Steve", "Hello")
+        );
+        assert_eq!(
+            parse(r#"[12:00:00 INFO]: Done (5,123s)! For help, type "help" or "?""#),
+            Some(ServerEvent::Done(Duration::from_millis(5123)))
+        );
+        assert_eq!(
+            parse("[12:00:00 INFO]: Stopping server"),
+            Some(ServerEvent::Stopping)
+        );
+        assert_eq!(
+            parse("[12:00:00 SEVERE]: java.lang.RuntimeException: Broken plugin"),
+            Some(ServerEvent::Exception(String::from(
+                "java.lang.RuntimeException: Broken plugin"
+            )))
+        );
+    }
+
+    #[test]
+    fn forge() {
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] [minecraft/MinecraftServer]: <Steve> Hello"),
+            chat("Steve", "Hello")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] [minecraft/MinecraftServer]: [Server] Hi"),
+            say("Server", "Hi")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] [minecraft/MinecraftServer]: Steve joined the game"),
+            join("Steve")
+        );
+        assert_eq!(
+            parse(
+                r#"[12:00:00] [Server thread/INFO] [minecraft/DedicatedServer]: Done (20.123s)! For help, type "help""#
+            ),
+            Some(ServerEvent::Done(Duration::from_millis(20123)))
+        );
+        assert_eq!(
+            parse("[12:00:00] [main/INFO] [cpw.mods.modlauncher.Launcher/MODLAUNCHER]: ModLauncher running: args [--launchTarget, forgeserver]"),
+            Some(ServerEvent::Progress(StartupPhase::LoadingMods))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] [somemod/]: [SomeMod] Loaded 12 recipes"),
+            None
+        );
+    }
+
+    #[test]
+    fn fabric() {
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] (Minecraft) <Steve> Hello"),
+            chat("Steve", "Hello")
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] (Minecraft) Steve left the game"),
+            Some(ServerEvent::Leave {
+                player: String::from("Steve"),
+            })
+        );
+        assert_eq!(
+            parse("[12:00:00] [main/INFO] (FabricLoader/GameProvider) Loading Minecraft 1.20.1 with Fabric Loader 0.14.21"),
+            Some(ServerEvent::Progress(StartupPhase::LoadingMods))
+        );
+        assert_eq!(
+            parse(
+                r#"[12:00:00] [Server thread/INFO] (Minecraft) Done (8.765s)! For help, type "help""#
+            ),
+            Some(ServerEvent::Done(Duration::from_millis(8765)))
+        );
+        assert_eq!(
+            parse("[12:00:00] [Server thread/INFO] (somemod) [SomeMod] Ready"),
+            None
+        );
+    }
+}
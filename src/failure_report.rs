@@ -0,0 +1,83 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Collects what the chat is told about a server that failed.
+
+use crate::events::ServerEvent;
+use crate::formatting::FormattedText;
+use crate::LOCALES;
+use fluent_templates::{LanguageIdentifier, Loader};
+use frankenstein::{MessageEntity, MessageEntityType};
+use std::collections::VecDeque;
+
+/// Number of log lines shown.
+const LOG_TAIL_LINES: usize = 10;
+/// Number of lines of stack traces shown, the last ones usually name the cause.
+const EXCEPTION_LINES: usize = 5;
+/// Longer lines are cut, so that the report fits into a single message.
+const MAX_LINE_LENGTH: usize = 150;
+
+/// Remembers the last lines of the log and the last lines of stack traces in it.
+#[derive(Debug, Default)]
+pub struct FailureReport {
+    log_tail: VecDeque<String>,
+    exceptions: VecDeque<String>,
+}
+
+impl FailureReport {
+    /// Records a line of the log, `event` is what the line was parsed as.
+    pub fn record(&mut self, line: &str, event: Option<&ServerEvent>) {
+        push_limited(&mut self.log_tail, line, LOG_TAIL_LINES);
+        if let Some(ServerEvent::Exception(exception)) = event {
+            push_limited(&mut self.exceptions, exception, EXCEPTION_LINES);
+        }
+    }
+
+    /// Formats the report below `headline`, with the exceptions and the log in code blocks.
+    pub fn format(
+        &self,
+        headline: &str,
+        locale: &LanguageIdentifier,
+    ) -> (String, Vec<MessageEntity>) {
+        let mut formatted = FormattedText::default();
+        formatted.push(headline);
+        for (text_id, lines) in [
+            ("report-exceptions", &self.exceptions),
+            ("report-log-tail", &self.log_tail),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            formatted.push(&format!("\n\n{}\n", LOCALES.lookup(locale, text_id)));
+            formatted.push_styled(
+                &lines.iter().cloned().collect::<Vec<String>>().join("\n"),
+                MessageEntityType::Pre,
+            );
+        }
+        formatted.into_parts()
+    }
+}
+
+fn push_limited(lines: &mut VecDeque<String>, line: &str, limit: usize) {
+    if lines.len() == limit {
+        lines.pop_front();
+    }
+    let line = match line.char_indices().nth(MAX_LINE_LENGTH) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => String::from(line),
+    };
+    lines.push_back(line);
+}
@@ -0,0 +1,396 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use frankenstein::{MessageEntity, MessageEntityType};
+use serde_json::{Map, Value};
+
+/// Length of `text` in UTF-16 code units, the unit Telegram measures entity offsets in.
+pub fn utf16_len(text: &str) -> u16 {
+    text.encode_utf16().count() as u16
+}
+
+/// Creates an entity of the given type spanning the first occurrence of `part` in `text`.
+pub fn entity_for(text: &str, part: &str, type_field: MessageEntityType) -> Option<MessageEntity> {
+    if part.is_empty() {
+        return None;
+    }
+    text.find(part).map(|start| {
+        MessageEntity::builder()
+            .type_field(type_field)
+            .offset(utf16_len(&text[..start]))
+            .length(utf16_len(part))
+            .build()
+    })
+}
+
+/// Text with formatting entities, used for text from Minecraft.
+///
+/// The entities are sent along with the plain text instead of using HTML, so the text needs no
+/// escaping and characters like `<` and `&` in messages of players are shown as they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormattedText {
+    text: String,
+    entities: Vec<MessageEntity>,
+}
+
+/// The formatting of Minecraft text that has a counterpart in Telegram.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+}
+
+impl Style {
+    fn entity_types(self) -> [(bool, MessageEntityType); 5] {
+        [
+            (self.bold, MessageEntityType::Bold),
+            (self.italic, MessageEntityType::Italic),
+            (self.underlined, MessageEntityType::Underline),
+            (self.strikethrough, MessageEntityType::Strikethrough),
+            (self.obfuscated, MessageEntityType::Spoiler),
+        ]
+    }
+
+    /// Applies the formatting of a JSON text component on top of this style.
+    fn with_component(mut self, component: &Map<String, Value>) -> Self {
+        let flag = |key: &str| match component.get(key) {
+            Some(Value::Bool(value)) => Some(*value),
+            Some(Value::String(value)) => Some(value == "true"),
+            _ => None,
+        };
+        self.bold = flag("bold").unwrap_or(self.bold);
+        self.italic = flag("italic").unwrap_or(self.italic);
+        self.underlined = flag("underlined").unwrap_or(self.underlined);
+        self.strikethrough = flag("strikethrough").unwrap_or(self.strikethrough);
+        self.obfuscated = flag("obfuscated").unwrap_or(self.obfuscated);
+        self
+    }
+}
+
+impl FormattedText {
+    /// Converts text written by Minecraft, which may be a JSON text component or contain `§`
+    /// formatting codes. Colours are dropped, the other formatting is converted to entities.
+    pub fn from_minecraft(text: &str) -> Self {
+        let mut runs = Vec::new();
+        let trimmed = text.trim();
+        let json = if (trimmed.starts_with('{') && trimmed.ends_with('}'))
+            || (trimmed.starts_with('[') && trimmed.ends_with(']'))
+        {
+            // Plain text like `[1, 2]` is valid JSON as well, but no text component.
+            serde_json::from_str::<Value>(trimmed)
+                .ok()
+                .filter(|value| match value {
+                    Value::Object(_) => true,
+                    Value::Array(components) => components.iter().any(Value::is_object),
+                    _ => false,
+                })
+        } else {
+            None
+        };
+        match json {
+            Some(component) => push_component(&component, Style::default(), &mut runs),
+            None => push_legacy(text, Style::default(), &mut runs),
+        }
+        FormattedText::from_runs(&runs)
+    }
+
+    fn from_runs(runs: &[(String, Style)]) -> Self {
+        let mut formatted = FormattedText::default();
+        let mut open: [Option<u16>; 5] = [None; 5];
+        // The empty unformatted run at the end closes all entities.
+        let end = (String::new(), Style::default());
+        for (text, style) in runs.iter().chain([&end]) {
+            let offset = utf16_len(&formatted.text);
+            for (index, (enabled, type_field)) in style.entity_types().into_iter().enumerate() {
+                match (open[index], enabled) {
+                    (None, true) => open[index] = Some(offset),
+                    (Some(start), false) => {
+                        if offset > start {
+                            formatted.entities.push(
+                                MessageEntity::builder()
+                                    .type_field(type_field)
+                                    .offset(start)
+                                    .length(offset - start)
+                                    .build(),
+                            );
+                        }
+                        open[index] = None;
+                    }
+                    _ => {}
+                }
+            }
+            formatted.text.push_str(text);
+        }
+        formatted
+    }
+
+    pub fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    pub fn push_styled(&mut self, text: &str, type_field: MessageEntityType) {
+        if !text.is_empty() {
+            self.entities.push(
+                MessageEntity::builder()
+                    .type_field(type_field)
+                    .offset(utf16_len(&self.text))
+                    .length(utf16_len(text))
+                    .build(),
+            );
+        }
+        self.text.push_str(text);
+    }
+
+    pub fn append(&mut self, other: FormattedText) {
+        let offset = utf16_len(&self.text);
+        self.entities
+            .extend(other.entities.into_iter().map(|mut entity| {
+                entity.offset += offset;
+                entity
+            }));
+        self.text.push_str(&other.text);
+    }
+
+    pub fn into_parts(self) -> (String, Vec<MessageEntity>) {
+        (self.text, self.entities)
+    }
+}
+
+/// Removes `§` formatting codes and other formatting from text written by Minecraft.
+pub fn strip_formatting(text: &str) -> String {
+    FormattedText::from_minecraft(text).text
+}
+
+/// Splits a JSON text component into runs of equally formatted text.
+fn push_component(component: &Value, parent: Style, runs: &mut Vec<(String, Style)>) {
+    match component {
+        Value::String(text) => push_legacy(text, parent, runs),
+        Value::Array(components) => {
+            for component in components {
+                push_component(component, parent, runs);
+            }
+        }
+        Value::Object(object) => {
+            let style = parent.with_component(object);
+            if let Some(text) = object.get("text").and_then(Value::as_str) {
+                push_legacy(text, style, runs);
+            } else if let Some(key) = object.get("translate").and_then(Value::as_str) {
+                // The translations are only known to the client, the arguments are shown at least.
+                match object.get("with").and_then(Value::as_array) {
+                    Some(arguments) => {
+                        for (index, argument) in arguments.iter().enumerate() {
+                            if index > 0 {
+                                runs.push((String::from(" "), style));
+                            }
+                            push_component(argument, style, runs);
+                        }
+                    }
+                    None => runs.push((String::from(key), style)),
+                }
+            } else if let Some(text) = ["selector", "keybind"]
+                .iter()
+                .find_map(|key| object.get(*key).and_then(Value::as_str))
+            {
+                runs.push((String::from(text), style));
+            }
+            if let Some(extra) = object.get("extra") {
+                push_component(extra, style, runs);
+            }
+        }
+        Value::Number(number) => runs.push((number.to_string(), parent)),
+        Value::Bool(value) => runs.push((value.to_string(), parent)),
+        Value::Null => {}
+    }
+}
+
+/// Splits text with `§` formatting codes into runs of equally formatted text.
+fn push_legacy(text: &str, base: Style, runs: &mut Vec<(String, Style)>) {
+    let mut style = base;
+    let mut current = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '§' {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            runs.push((std::mem::take(&mut current), style));
+        }
+        match chars.next().map(|code| code.to_ascii_lowercase()) {
+            Some('l') => style.bold = true,
+            Some('o') => style.italic = true,
+            Some('n') => style.underlined = true,
+            Some('m') => style.strikethrough = true,
+            Some('k') => style.obfuscated = true,
+            // Colours reset the formatting as well.
+            Some('r' | '0'..='9' | 'a'..='f') => style = base,
+            // Hex colours of Spigot are written as `§x§r§r§g§g§b§b`.
+            Some('x') => {
+                chars.by_ref().take(12).for_each(drop);
+                style = base;
+            }
+            _ => {}
+        }
+    }
+    if !current.is_empty() {
+        runs.push((current, style));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frankenstein::MessageEntityType::{Bold, Italic, Spoiler, Strikethrough, Underline};
+
+    fn convert(text: &str) -> (String, Vec<(MessageEntityType, u16, u16)>) {
+        let (text, entities) = FormattedText::from_minecraft(text).into_parts();
+        let entities = entities
+            .into_iter()
+            .map(|entity| (entity.type_field, entity.offset, entity.length))
+            .collect();
+        (text, entities)
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(
+            convert("1 < 2 & <b>not html</b>"),
+            (String::from("1 < 2 & <b>not html</b>"), vec![])
+        );
+    }
+
+    #[test]
+    fn formatting_codes() {
+        assert_eq!(
+            convert("§lBold§r normal §oitalic"),
+            (
+                String::from("Bold normal italic"),
+                vec![(Bold, 0, 4), (Italic, 12, 6)]
+            )
+        );
+        assert_eq!(
+            convert("§NUnder§MStrike§kSecret"),
+            (
+                String::from("UnderStrikeSecret"),
+                vec![(Underline, 0, 17), (Strikethrough, 5, 12), (Spoiler, 11, 6)]
+            )
+        );
+    }
+
+    #[test]
+    fn colours_reset_formatting() {
+        assert_eq!(
+            convert("§lbold§cred§lagain"),
+            (
+                String::from("boldredagain"),
+                vec![(Bold, 0, 4), (Bold, 7, 5)]
+            )
+        );
+    }
+
+    #[test]
+    fn hex_colours() {
+        assert_eq!(
+            convert("§x§f§f§0§0§8§8pink§lbold"),
+            (String::from("pinkbold"), vec![(Bold, 4, 4)])
+        );
+    }
+
+    #[test]
+    fn json_component() {
+        let json = r#"{"text": "Hi ", "bold": true, "extra": [{"text": "there", "bold": false, "italic": "true"}, "!"]}"#;
+        assert_eq!(
+            convert(json),
+            (
+                String::from("Hi there!"),
+                vec![(Bold, 0, 3), (Italic, 3, 5), (Bold, 8, 1)]
+            )
+        );
+    }
+
+    #[test]
+    fn json_translate() {
+        let json =
+            r#"{"translate": "chat.type.text", "with": [{"text": "Alex", "bold": true}, "hello"]}"#;
+        assert_eq!(
+            convert(json),
+            (String::from("Alex hello"), vec![(Bold, 0, 4)])
+        );
+        assert_eq!(
+            convert(r#"{"translate": "multiplayer.player.joined"}"#),
+            (String::from("multiplayer.player.joined"), vec![])
+        );
+    }
+
+    #[test]
+    fn json_array() {
+        assert_eq!(
+            convert(r#"["", {"text": "Alex", "bold": true}, ": §ohi"]"#),
+            (String::from("Alex: hi"), vec![(Bold, 0, 4), (Italic, 6, 2)])
+        );
+    }
+
+    #[test]
+    fn json_looking_text() {
+        for text in [
+            "[1, 2]",
+            "{not json}",
+            "[Server] hello",
+            "{}x",
+            "[\"a\", \"b\"]",
+        ] {
+            assert_eq!(convert(text), (String::from(text), vec![]), "{}", text);
+        }
+    }
+
+    #[test]
+    fn utf16_offsets() {
+        // The emoji takes two UTF-16 code units, the accented letter one.
+        assert_eq!(
+            convert("§l😀§r é §lok"),
+            (String::from("😀 é ok"), vec![(Bold, 0, 2), (Bold, 5, 2)])
+        );
+    }
+
+    #[test]
+    fn strip() {
+        assert_eq!(strip_formatting("§6§lAlex"), "Alex");
+        assert_eq!(
+            strip_formatting(r#"{"text": "Sam", "italic": true}"#),
+            "Sam"
+        );
+    }
+
+    #[test]
+    fn append_shifts_entities() {
+        let mut formatted = FormattedText::default();
+        formatted.push_styled("😀 Alex", Bold);
+        formatted.push(": ");
+        formatted.append(FormattedText::from_minecraft("§ohi"));
+        let (text, entities) = formatted.into_parts();
+        assert_eq!(text, "😀 Alex: hi");
+        assert_eq!(
+            entities
+                .iter()
+                .map(|entity| (entity.offset, entity.length))
+                .collect::<Vec<_>>(),
+            vec![(0, 7), (9, 2)]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Stops servers nobody has been playing on for a while.
+
+use crate::error::BotError;
+use crate::BotData;
+use fluent_templates::fluent_bundle::FluentValue;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+/// Time between two requests for the player list.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Stops the server once nobody was online for `idle_minutes`, until the bot shuts down.
+pub async fn run_idle_shutdown(bot_data: BotData, server_name: String, idle_minutes: u64) {
+    let idle_timeout = Duration::from_secs(idle_minutes * 60);
+    let server = &bot_data.servers[&server_name];
+    let mut check = interval(IDLE_CHECK_INTERVAL);
+    check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Since when the running server has been empty.
+    let mut empty_since: Option<Instant> = None;
+
+    loop {
+        check.tick().await;
+        if bot_data.is_kept_alive(&server_name).await {
+            empty_since = None;
+            continue;
+        }
+        let online = match server.rcon.execute("list").await {
+            Ok(reply) => match bot_data.player_list_parser.parse(&reply) {
+                Some(player_list) => player_list.online,
+                None => {
+                    BotError::Parse(format!("unexpected player list: {}", reply))
+                        .log("idle_shutdown", None);
+                    empty_since = None;
+                    continue;
+                }
+            },
+            // The server is stopped or still starting.
+            Err(_) => {
+                empty_since = None;
+                continue;
+            }
+        };
+        if online > 0 {
+            empty_since = None;
+            continue;
+        }
+        let since = *empty_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < idle_timeout {
+            continue;
+        }
+
+        empty_since = None;
+        println!(
+            "Nobody was online on server {} for {} minutes.",
+            server_name, idle_minutes
+        );
+        let chat_ids = bot_data.chats_of_server(&server_name);
+        if let Err(err) = bot_data.stop_server(&server_name, &chat_ids).await {
+            err.log("idle_shutdown", None);
+            continue;
+        }
+        let text = bot_data.server_text(&server_name, "idle-shutdown", {
+            let mut map = HashMap::new();
+            map.insert(String::from("minutes"), FluentValue::from(idle_minutes));
+            map
+        });
+        for chat_id in chat_ids {
+            bot_data.send_message(chat_id, &text).await;
+        }
+    }
+}
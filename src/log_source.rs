@@ -0,0 +1,313 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use async_process::{Command as AsyncCommand, Stdio};
+use async_trait::async_trait;
+use futures_lite::io::BufReader;
+use futures_lite::{AsyncBufReadExt, StreamExt};
+use std::fmt::Debug;
+use std::io;
+use std::io::SeekFrom;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{
+    AsyncBufReadExt as TokioAsyncBufReadExt, AsyncSeekExt, BufReader as TokioBufReader,
+};
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+/// Number of lines buffered between the follower and the reader of a log.
+const LOG_CHANNEL_CAPACITY: usize = 256;
+
+/// How often a followed log file is checked for new lines and rotation.
+const FILE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A log of a Minecraft server that can be followed line by line.
+#[async_trait]
+pub trait LogSource: Debug + Send + Sync {
+    /// Follows the log, starting with the first line written after this call.
+    ///
+    /// The log is followed until the returned receiver is dropped.
+    async fn follow(&self) -> io::Result<mpsc::Receiver<String>>;
+}
+
+/// Follows the journal of a systemd unit via `sudo journalctl`.
+#[derive(Debug)]
+pub struct JournaldLogSource {
+    unit: String,
+}
+
+impl JournaldLogSource {
+    pub fn new(unit: String) -> Self {
+        JournaldLogSource { unit }
+    }
+}
+
+#[async_trait]
+impl LogSource for JournaldLogSource {
+    async fn follow(&self) -> io::Result<mpsc::Receiver<String>> {
+        let mut child = AsyncCommand::new("sudo")
+            .args(["journalctl", "-f", "-n", "0", "-u", &self.unit])
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (tx, rx) = mpsc::channel(LOG_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stdout).lines();
+            loop {
+                tokio::select! {
+                    line = reader.next() => match line {
+                        Some(Ok(line)) => {
+                            if tx.send(line).await.is_err() {
+                                break;
+                            }
+                        }
+                        _ => break,
+                    },
+                    // Do not wait for the next line to notice that nobody listens anymore.
+                    _ = tx.closed() => break,
+                }
+            }
+            if let Err(err) = child.kill() {
+                println!("Failed to stop journalctl: {}", err);
+            }
+            if let Err(err) = child.status().await {
+                println!("Failed to wait for journalctl: {}", err);
+            }
+        });
+        Ok(rx)
+    }
+}
+
+/// Follows a log file like `logs/latest.log`.
+///
+/// The file is reopened whenever it is replaced or truncated, which is what the server does when
+/// it rotates its log at midnight or on restart.
+/// A file that does not exist yet is waited for.
+#[derive(Debug)]
+pub struct FileLogSource {
+    path: PathBuf,
+}
+
+impl FileLogSource {
+    pub fn new(path: PathBuf) -> Self {
+        FileLogSource { path }
+    }
+}
+
+#[async_trait]
+impl LogSource for FileLogSource {
+    async fn follow(&self) -> io::Result<mpsc::Receiver<String>> {
+        // The server creates the file when it starts, until then it is waited for.
+        let (mut reader, mut inode, mut position) = match File::open(&self.path).await {
+            Ok(mut file) => {
+                let inode = file.metadata().await?.ino();
+                let position = file.seek(SeekFrom::End(0)).await?;
+                (Some(TokioBufReader::new(file)), inode, position)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                println!(
+                    "Log {} does not exist yet, waiting for it.",
+                    self.path.display()
+                );
+                (None, 0, 0)
+            }
+            Err(err) => return Err(err),
+        };
+        let path = self.path.clone();
+        let (tx, rx) = mpsc::channel(LOG_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            let mut line = Vec::new();
+            let mut reopen = false;
+            loop {
+                if let Some(reader) = reader.as_mut() {
+                    match reader.read_until(b'\n', &mut line).await {
+                        Ok(0) => {}
+                        Ok(read) => {
+                            position += read as u64;
+                            // Incomplete lines stay in the buffer until the rest is written.
+                            if line.ends_with(b"\n") {
+                                let text = String::from_utf8_lossy(&line)
+                                    .trim_end_matches(['\n', '\r'])
+                                    .to_string();
+                                line.clear();
+                                if tx.send(text).await.is_err() {
+                                    return;
+                                }
+                            }
+                            continue;
+                        }
+                        Err(err) => {
+                            println!("Failed to read log {}: {}", path.display(), err);
+                        }
+                    }
+                }
+
+                // The old file has been read completely, continue with the new one.
+                if reopen {
+                    reopen = false;
+                    match File::open(&path).await {
+                        Ok(new_file) => {
+                            if reader.is_some() {
+                                println!("Log {} was rotated, reopening it.", path.display());
+                            } else {
+                                println!("Log {} was created, following it.", path.display());
+                            }
+                            if let Ok(metadata) = new_file.metadata().await {
+                                inode = metadata.ino();
+                            }
+                            reader = Some(TokioBufReader::new(new_file));
+                            position = 0;
+                            line.clear();
+                            continue;
+                        }
+                        Err(err) => {
+                            println!("Failed to reopen log {}: {}", path.display(), err);
+                        }
+                    }
+                }
+
+                if tx.is_closed() {
+                    return;
+                }
+                sleep(FILE_POLL_INTERVAL).await;
+
+                reopen = match tokio::fs::metadata(&path).await {
+                    Ok(metadata) => {
+                        reader.is_none() || metadata.ino() != inode || metadata.len() < position
+                    }
+                    // The new file is not created yet.
+                    Err(_) => false,
+                };
+            }
+        });
+        Ok(rx)
+    }
+}
+
+/// Stands in for a real log in tests, the given lines are the whole log.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct FakeLogSource {
+    pub lines: Vec<String>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl LogSource for FakeLogSource {
+    async fn follow(&self) -> io::Result<mpsc::Receiver<String>> {
+        let (tx, rx) = mpsc::channel(LOG_CHANNEL_CAPACITY.max(self.lines.len()));
+        for line in &self.lines {
+            // Cannot fail, the receiver is still there and the channel is large enough.
+            let _ = tx.try_send(line.clone());
+        }
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    /// A path for a log file of its own for every test.
+    fn temp_log(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.log", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn append(path: &PathBuf, text: &str) {
+        use tokio::io::AsyncWriteExt;
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .await
+            .unwrap();
+        file.write_all(text.as_bytes()).await.unwrap();
+    }
+
+    async fn next_line(log: &mut mpsc::Receiver<String>) -> Option<String> {
+        timeout(Duration::from_secs(5), log.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn follows_new_lines_only() {
+        let path = temp_log("new-lines");
+        append(&path, "before\n").await;
+        let mut log = FileLogSource::new(path.clone()).follow().await.unwrap();
+        append(&path, "after\npartial").await;
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("after"));
+        append(&path, " line\n").await;
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("partial line"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn follows_renamed_log() {
+        let path = temp_log("renamed");
+        let rotated = path.with_extension("log.1");
+        append(&path, "before\n").await;
+        let mut log = FileLogSource::new(path.clone()).follow().await.unwrap();
+        append(&path, "old file\n").await;
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("old file"));
+
+        // Lines written to the old file before the new one appears are not lost.
+        append(&path, "last line\n").await;
+        std::fs::rename(&path, &rotated).unwrap();
+        append(&path, "new file\n").await;
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("last line"));
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("new file"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rotated).unwrap();
+    }
+
+    #[tokio::test]
+    async fn follows_truncated_log() {
+        let path = temp_log("truncated");
+        append(&path, "a rather long line written before\n").await;
+        let mut log = FileLogSource::new(path.clone()).follow().await.unwrap();
+        append(&path, "old content\n").await;
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("old content"));
+
+        tokio::fs::write(&path, "new\n").await.unwrap();
+        assert_eq!(next_line(&mut log).await.as_deref(), Some("new"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn waits_for_missing_file() {
+        let path = temp_log("missing");
+        let mut log = FileLogSource::new(path.clone()).follow().await.unwrap();
+
+        tokio::fs::write(&path, "[12:00:00] [Server thread/INFO]: Starting\n")
+            .await
+            .unwrap();
+        let line = timeout(Duration::from_secs(5), log.recv()).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            line.unwrap().as_deref(),
+            Some("[12:00:00] [Server thread/INFO]: Starting")
+        );
+    }
+}
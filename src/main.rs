@@ -14,157 +14,365 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+mod commands;
+mod config;
+mod error;
+mod events;
+mod failure_report;
+mod formatting;
+mod idle;
+mod log_source;
+mod outbox;
+mod player_list;
+mod rcon;
+mod schedule;
+mod server;
+mod service_manager;
+mod shutdown;
+mod startup;
+mod state;
+mod tellraw;
+mod watchdog;
+mod webhook;
+
+use crate::commands::{parse_command, Command, CommandError, CommandKind};
+use crate::config::{Config, Role};
+use crate::error::BotError;
+use crate::events::{AdvancementKind, EventParser, ServerEvent};
+use crate::failure_report::FailureReport;
+use crate::formatting::{entity_for, strip_formatting, FormattedText};
+use crate::idle::run_idle_shutdown;
+use crate::outbox::Outbox;
+use crate::player_list::{PlayerList, PlayerListParser};
+use crate::rcon::RconError;
+use crate::schedule::{action_text, run_schedule, upcoming_runs};
+use crate::server::Server;
+use crate::shutdown::{shutdown_signal, HandlerGuard, HandlerTracker};
+use crate::startup::{done_text, StartupProgress};
+use crate::state::BotState;
+use crate::tellraw::{media_component, sender_component, text_components};
+use crate::watchdog::run_watchdog;
+use crate::webhook::run_webhook;
 use crate::ServerStatus::{Inactive, Running, Starting};
-use async_process::Command as AsyncCommand;
-use fluent_templates::fluent_bundle::types::FluentNumber;
+use chrono::Utc;
 use fluent_templates::fluent_bundle::FluentValue;
 use fluent_templates::{static_loader, LanguageIdentifier, Loader};
 use frankenstein::MessageEntityType::Bold;
 use frankenstein::{
-    AnswerCallbackQueryParams, Api, CallbackQuery, EditMessageTextParams, GetUpdatesParams,
-    InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageEntity, ReplyMarkup,
-    SendMessageParams, TelegramApi,
+    AnswerCallbackQueryParams, AsyncApi, AsyncTelegramApi, BotCommand, CallbackQuery, Chat,
+    ChatMember, ChatType, DeleteWebhookParams, EditMessageTextParams, GetChatAdministratorsParams,
+    GetUpdatesParams, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageEntity,
+    ReplyMarkup, SendMessageParams, SetMyCommandsParams, Update, User,
 };
-use futures_lite::io::BufReader;
-use futures_lite::{AsyncBufReadExt, StreamExt};
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::process::{Command, Stdio};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::process;
+use std::str;
 use std::str::FromStr;
 use std::string::String;
-use std::sync::mpsc;
 use std::sync::Arc;
-use std::time::Duration;
-use std::{fs, str};
-use tokio::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::time::{interval, sleep, timeout, Instant};
 
 type ChatbridgeMap = Arc<RwLock<HashMap<String, JoinHandle<()>>>>;
 type EnableChatbridgeAfterStartMap = Arc<RwLock<HashMap<String, Message>>>;
+type ServerMap = Arc<HashMap<String, Server>>;
+type ChatAdminsMap = Arc<Mutex<HashMap<i64, (Instant, HashSet<u64>)>>>;
+
+/// File the state of the bot is persisted in, relative to the working directory.
+const STATE_FILE: &str = "bot-state.json";
+
+/// The kinds of updates the bot handles.
+const ALLOWED_UPDATES: [&str; 2] = ["message", "callback_query"];
+
+/// Waiting time before polling again after the first failure, doubled with every further failure.
+const POLLING_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const POLLING_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// How much longer than the polling timeout a request for updates may take before it is given up.
+const POLLING_TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// How long the handlers still running at shutdown may take to finish.
+const HANDLER_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a starting server is checked for having failed.
+const START_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// For how many hours `/keep_alive` suspends the idle shutdown, if not given, and at most.
+const KEEP_ALIVE_DEFAULT_HOURS: u32 = 2;
+const KEEP_ALIVE_MAX_HOURS: u32 = 24;
+
+/// How long the administrators of a chat are remembered before they are requested again.
+const CHAT_ADMINS_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// How long the messages still queued at shutdown may take to be sent.
+const OUTBOX_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DEATH_EMOJI: &str = "💀";
+const ADVANCEMENT_EMOJI: &str = "🏆";
+const CHALLENGE_EMOJI: &str = "🏅";
+const GOAL_EMOJI: &str = "🎯";
 
 static_loader! {
     static LOCALES = {
         // The directory of localisations and fluent resources.
         locales: "./locales",
         // The language to falback on if something is not present.
-        fallback_language: "en-UK"
+        fallback_language: "en-UK",
+        // Removes unicode isolating marks around arguments, so that player names can be found
+        // in the text to format them.
+        customise: |bundle| bundle.set_use_isolating(false),
     };
 }
 
 #[tokio::main]
 async fn main() {
     // Read configuration json
-    let config_file = fs::read_to_string("bot-config.json").expect("Error reading config file");
-    let config: Config =
-        serde_json::from_str(&config_file).expect("Could not parse config file. Aborting.");
+    let config = Config::load("bot-config.json").unwrap_or_else(|err| abort(err, "load_config"));
     let token = config.token.as_str();
     println!("Configs (incl. token) read successfully");
 
     // Construct api
-    let api = Api::new(token);
+    let api = AsyncApi::new(token);
 
-    //let bot_name = api.get_me().await.unwrap().result.username.unwrap();
+    let bot_username = match api.get_me().await {
+        Ok(response) => response.result.username.unwrap_or_else(|| {
+            abort(
+                BotError::Parse(String::from("the bot has no username")),
+                "get_me",
+            )
+        }),
+        Err(err) => abort(BotError::from(err), "get_me"),
+    };
+    println!("Running as bot @{}.", bot_username);
 
-    let update_params_builder = GetUpdatesParams::builder()
-        .allowed_updates(vec!["message".to_string(), "callback_query".to_string()]);
+    let locale = LanguageIdentifier::from_str(&config.locale).unwrap_or_else(|err| {
+        abort(
+            BotError::Config(format!("invalid locale {}: {}", config.locale, err)),
+            "load_config",
+        )
+    });
+    register_commands(&api, &locale).await;
+
+    let state = BotState::load(STATE_FILE);
 
-    let mut update_params = update_params_builder.clone().build();
+    let servers = config
+        .servers
+        .iter()
+        .map(|(server_name, server_config)| {
+            (server_name.clone(), Server::new(server_name, server_config))
+        })
+        .collect();
 
     let bot_data = BotData {
-        locale: LanguageIdentifier::from_str(&config.locale)
-            .expect("Could not parse language identifier."),
-        api,
+        locale,
+        bot_username,
+        api: api.clone(),
         config,
         chatbridge_map: Arc::new(RwLock::new(HashMap::new())),
         enable_chatbridge_after_start_map: Arc::new(RwLock::new(HashMap::new())),
+        servers: Arc::new(servers),
+        state: Arc::new(Mutex::new(state.clone())),
+        outbox: Outbox::new(api.clone()),
+        expected_stops: Arc::new(Mutex::new(HashSet::new())),
+        keep_alive: Arc::new(Mutex::new(HashMap::new())),
+        chat_admins: Arc::new(Mutex::new(HashMap::new())),
+        player_list_parser: PlayerListParser::new(),
     };
+    bot_data.restore_state(state).await;
+
+    for (server_name, server_config) in &bot_data.config.servers {
+        if server_config.watchdog.enabled {
+            tokio::spawn(run_watchdog(bot_data.clone(), server_name.clone()));
+        }
+        for index in 0..server_config.schedules.len() {
+            tokio::spawn(run_schedule(bot_data.clone(), server_name.clone(), index));
+        }
+        if let Some(idle_minutes) = server_config.idle_shutdown {
+            tokio::spawn(run_idle_shutdown(
+                bot_data.clone(),
+                server_name.clone(),
+                idle_minutes,
+            ));
+        }
+    }
+
+    let handler_tracker = HandlerTracker::new();
+    match &bot_data.config.webhook {
+        Some(webhook) => {
+            if let Err(err) =
+                run_webhook(&bot_data, webhook, &handler_tracker, shutdown_signal()).await
+            {
+                abort(err, "run_webhook");
+            }
+        }
+        None => poll_updates(&bot_data, &handler_tracker, shutdown_signal()).await,
+    }
+
+    println!("Shutting down, waiting for running handlers.");
+    if !handler_tracker.wait(HANDLER_SHUTDOWN_TIMEOUT).await {
+        println!(
+            "Handlers did not finish within {}s, aborted them.",
+            HANDLER_SHUTDOWN_TIMEOUT.as_secs()
+        );
+    }
+    bot_data.pause_chatbridges().await;
+    bot_data.shutdown_servers().await;
+    if !bot_data.outbox.flush(OUTBOX_FLUSH_TIMEOUT).await {
+        println!(
+            "Not all messages could be sent within {}s.",
+            OUTBOX_FLUSH_TIMEOUT.as_secs()
+        );
+    }
+    println!("Shut down.");
+}
+
+/// The start message shows the status of the start beneath the text.
+fn start_message_text(text: &str, status: &str) -> String {
+    if status.is_empty() {
+        String::from(text)
+    } else {
+        format!("{}\n\n{}", text, status)
+    }
+}
+
+/// The status last shown in the start message, empty if none was shown yet.
+fn shown_status(start_message: &Message) -> &str {
+    start_message
+        .text
+        .as_deref()
+        .and_then(|text| text.split_once("\n\n"))
+        .map_or("", |(_, status)| status)
+}
+
+/// Logs an error the bot cannot start without and exits.
+fn abort(err: BotError, context: &str) -> ! {
+    err.log(context, None);
+    println!("Aborting.");
+    process::exit(1)
+}
+
+/// Requests updates from Telegram and handles them, until `shutdown` completes.
+async fn poll_updates(
+    bot_data: &BotData,
+    handler_tracker: &HandlerTracker,
+    shutdown: impl Future<Output = ()>,
+) {
+    // Updates cannot be requested while a webhook is set.
+    let delete_webhook_params = DeleteWebhookParams::builder().build();
+    if let Err(err) = bot_data.api.delete_webhook(&delete_webhook_params).await {
+        println!("Failed to delete webhook: {:?}", err);
+    }
+
+    let update_params_builder = GetUpdatesParams::builder()
+        .allowed_updates(ALLOWED_UPDATES.map(String::from).to_vec())
+        .timeout(bot_data.config.polling_timeout);
+    let mut update_params = match bot_data.state.lock().await.update_offset {
+        Some(offset) => update_params_builder.clone().offset(offset).build(),
+        None => update_params_builder.clone().build(),
+    };
+
+    // A connection dropped silently would otherwise leave the request waiting forever.
+    let request_timeout =
+        Duration::from_secs(bot_data.config.polling_timeout.into()) + POLLING_TIMEOUT_MARGIN;
+    tokio::pin!(shutdown);
+    let mut backoff = POLLING_BACKOFF_MIN;
 
     println!("Start update loop.");
     loop {
-        let result = bot_data.api.get_updates(&update_params);
+        let result = tokio::select! {
+            _ = &mut shutdown => break,
+            result = timeout(request_timeout, bot_data.api.get_updates(&update_params)) => {
+                match result {
+                    Ok(result) => result.map_err(|err| format!("{:?}", err)),
+                    Err(_) => Err(format!("no response within {}s", request_timeout.as_secs())),
+                }
+            }
+        };
 
         match result {
             Ok(response) => {
+                backoff = POLLING_BACKOFF_MIN;
+                if let Some(last_update) = response.result.last() {
+                    let offset = last_update.update_id + 1;
+                    update_params = update_params_builder.clone().offset(offset).build();
+                    // Saved before handling the updates, so that none is handled twice after a crash.
+                    bot_data.save_update_offset(offset).await;
+                }
                 for update in response.result {
-                    if let Some(message) = update.message {
-                        if bot_data
-                            .config
-                            .chat_server_map
-                            .contains_key(&message.chat.id.to_string())
-                        {
-                            println!(
-                                "Message received from {:}, handling enabled.",
-                                message.chat.id
-                            );
-                            let mut bot_data_clone = bot_data.clone();
-
-                            tokio::spawn(async move {
-                                bot_data_clone.process_message(message).await;
-                            });
-                        } else {
-                            println!(
-                                "Message received from {:}, no handling enabled.",
-                                message.chat.id
-                            );
-                        }
-                    } else if let Some(callback_query) = update.callback_query {
-                        if callback_query.message.as_ref().is_some() {
-                            if bot_data.config.chat_server_map.contains_key(
-                                &callback_query.message.as_ref().unwrap().chat.id.to_string(),
-                            ) {
-                                println!(
-                                    "Callback query received from {:}, handling enabled.",
-                                    callback_query.message.as_ref().unwrap().chat.id
-                                );
-                                let mut bot_data_clone = bot_data.clone();
-
-                                tokio::spawn(async move {
-                                    bot_data_clone.process_callback_query(callback_query).await;
-                                });
-                            } else {
-                                println!(
-                                    "Callback query received from {:}, no handling enabled.",
-                                    callback_query.message.as_ref().unwrap().chat.id
-                                );
-                            }
-                        } else {
-                            println!(
-                                "Callback query received from unknown sender, no handling enabled.",
-                            );
-                        }
-                    }
-                    update_params = update_params_builder
-                        .clone()
-                        .offset(update.update_id + 1)
-                        .build();
+                    bot_data.dispatch_update(update, handler_tracker.track());
                 }
             }
             Err(error) => {
-                println!("Failed to get updates: {:?}", error);
+                println!(
+                    "Failed to get updates, retrying in {}s: {}",
+                    backoff.as_secs(),
+                    error
+                );
+                tokio::select! {
+                    _ = &mut shutdown => break,
+                    _ = sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(POLLING_BACKOFF_MAX);
             }
         }
     }
 }
 
+/// Registers the command list shown by Telegram clients, once for every locale.
+async fn register_commands(api: &AsyncApi, default_locale: &LanguageIdentifier) {
+    let commands = |locale: &LanguageIdentifier| {
+        CommandKind::ALL
+            .into_iter()
+            .map(|command| {
+                BotCommand::builder()
+                    .command(command.name())
+                    .description(LOCALES.lookup(locale, command.description_id()))
+                    .build()
+            })
+            .collect::<Vec<BotCommand>>()
+    };
+
+    let mut params = vec![SetMyCommandsParams::builder()
+        .commands(commands(default_locale))
+        .build()];
+    for locale in LOCALES.locales() {
+        params.push(
+            SetMyCommandsParams::builder()
+                .commands(commands(locale))
+                .language_code(locale.language.as_str())
+                .build(),
+        );
+    }
+    for params in params {
+        if let Err(err) = api.set_my_commands(&params).await {
+            BotError::from(err).log("register_commands", None);
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct BotData {
-    api: Api,
+    api: AsyncApi,
     config: Config,
     locale: LanguageIdentifier,
+    bot_username: String,
     chatbridge_map: ChatbridgeMap,
     enable_chatbridge_after_start_map: EnableChatbridgeAfterStartMap,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct Config {
-    token: String,
-    rcon_password: String,
-    locale: String,
-    chat_server_map: HashMap<String, String>,
+    servers: ServerMap,
+    /// The state as saved last. Held while the state is saved, so that saves do not interleave.
+    state: Arc<Mutex<BotState>>,
+    outbox: Outbox,
+    /// Servers being stopped by the bot, so that the watchdog does not take it for a crash.
+    expected_stops: Arc<Mutex<HashSet<String>>>,
+    /// Until when the idle shutdown of a server is suspended.
+    keep_alive: Arc<Mutex<HashMap<String, Instant>>>,
+    /// The user ids of the administrators of a chat, and when they were requested.
+    chat_admins: ChatAdminsMap,
+    player_list_parser: PlayerListParser,
 }
 
 #[derive(PartialEq)]
@@ -172,169 +380,266 @@ enum ServerStatus {
     Inactive,
     Starting,
     Running {
-        current_players: String,
-        max_players: String,
-        players: String,
+        /// `None` if the reply to `list` could not be parsed.
+        players: Option<PlayerList>,
     },
 }
 
 impl BotData {
-    async fn process_message(&mut self, message: Message) {
-        if let Some(text) = &message.text {
-            if text.starts_with("/start_server") {
-                self.start_server_handler(message).await;
-            } else if text.starts_with("/stop_server") {
-                self.stop_server_handler(message).await;
-            } else if text.starts_with("/status_server") {
-                self.status_server_handler(message).await;
-            } else if text.starts_with("/enable_chatbridge") {
-                self.enable_chatbridge_handler(message).await;
-            } else if text.starts_with("/disable_chatbridge") {
-                self.disable_chatbridge_handler(message, true).await;
-            } else if text.starts_with("/licence") {
-                self.licence_handler(message).await;
+    /// Hands the update to its handler, which runs in a task of its own holding `guard`.
+    fn dispatch_update(&self, update: Update, guard: HandlerGuard) {
+        if let Some(message) = update.message {
+            if self.is_outdated(&message) {
+                println!(
+                    "Message received from {:} is older than {}s, dropping it.",
+                    message.chat.id, self.config.max_update_age
+                );
+            } else if self
+                .config
+                .chat_server_map
+                .contains_key(&message.chat.id.to_string())
+            {
+                println!(
+                    "Message received from {:}, handling enabled.",
+                    message.chat.id
+                );
+                let mut bot_data = self.clone();
+
+                guard.spawn(async move {
+                    bot_data.process_message(message).await;
+                });
             } else {
-                self.pass_message_to_chatbridge(message).await;
+                println!(
+                    "Message received from {:}, no handling enabled.",
+                    message.chat.id
+                );
+            }
+        } else if let Some(callback_query) = update.callback_query {
+            match callback_query
+                .message
+                .as_ref()
+                .map(|message| message.chat.id)
+            {
+                Some(chat_id)
+                    if self
+                        .config
+                        .chat_server_map
+                        .contains_key(&chat_id.to_string()) =>
+                {
+                    println!(
+                        "Callback query received from {:}, handling enabled.",
+                        chat_id
+                    );
+                    let mut bot_data = self.clone();
+
+                    guard.spawn(async move {
+                        bot_data.process_callback_query(callback_query).await;
+                    });
+                }
+                Some(chat_id) => {
+                    println!(
+                        "Callback query received from {:}, no handling enabled.",
+                        chat_id
+                    );
+                }
+                None => {
+                    println!("Callback query received from unknown sender, no handling enabled.",);
+                }
             }
         }
     }
 
+    async fn process_message(&mut self, message: Message) {
+        match &message.text {
+            Some(text) => match parse_command(text, &self.bot_username) {
+                Some(Ok(command))
+                    if !self
+                        .has_role(&message, command.kind().required_role())
+                        .await =>
+                {
+                    self.permission_denied_handler(message).await
+                }
+                Some(Ok(command)) => {
+                    let context = command.kind().name();
+                    let result = match command {
+                        Command::StartServer => self.start_server_handler(message.clone()).await,
+                        Command::StopServer => self.stop_server_handler(message.clone()).await,
+                        Command::StatusServer => self.status_server_handler(message.clone()).await,
+                        Command::EnableChatbridge => {
+                            self.enable_chatbridge_handler(message.clone()).await
+                        }
+                        Command::KeepAlive { hours } => {
+                            self.keep_alive_handler(message.clone(), hours).await;
+                            Ok(())
+                        }
+                        Command::Schedule => self.schedule_handler(message.clone()).await,
+                        Command::DisableChatbridge => {
+                            self.disable_chatbridge_handler(message.clone(), true).await;
+                            Ok(())
+                        }
+                        Command::Licence => {
+                            self.licence_handler(message.clone()).await;
+                            Ok(())
+                        }
+                        Command::Help { command } => {
+                            self.help_handler(message.clone(), command).await;
+                            Ok(())
+                        }
+                    };
+                    if let Err(err) = result {
+                        self.error_handler(&message, context, err).await;
+                    }
+                }
+                Some(Err(err)) => self.command_error_handler(message, err).await,
+                // Commands for other bots are not passed on either.
+                None if text.starts_with('/') => {}
+                None => self.pass_message_to_chatbridge(message).await,
+            },
+            // Media, stickers and the like.
+            None => self.pass_message_to_chatbridge(message).await,
+        }
+    }
+
+    /// Logs an error of a handler and tells the sender of `message` about it.
+    async fn error_handler(&self, message: &Message, context: &str, err: BotError) {
+        err.log(context, Some(message.chat.id));
+        self.send_message_with_reply(message, &LOCALES.lookup(&self.locale, err.text_id()))
+            .await;
+    }
+
     async fn process_callback_query(&mut self, callback_query: CallbackQuery) {
         if let Some(callback_data) = &callback_query.data {
-            if callback_data == "inline_enable_chatbridge" {
-                self.enable_chatbridge_inline_handler(callback_query).await;
+            if let (Some(message), "inline_enable_chatbridge") =
+                (&callback_query.message, callback_data.as_str())
+            {
+                let chat_id = message.chat.id;
+                if !self
+                    .user_has_role(chat_id, Some(&callback_query.from), Role::Member)
+                    .await
+                {
+                    println!(
+                        "User {} is not allowed to enable the chat bridge in {}.",
+                        callback_query.from.id, chat_id
+                    );
+                    let answer_callback_query = AnswerCallbackQueryParams::builder()
+                        .callback_query_id(&callback_query.id)
+                        .text(LOCALES.lookup(&self.locale, "permission-denied"))
+                        .show_alert(true)
+                        .build();
+                    if let Err(err) = self.api.answer_callback_query(&answer_callback_query).await {
+                        BotError::from(err).log("answer_callback_query", Some(chat_id));
+                    }
+                } else {
+                    self.enable_chatbridge_inline_handler(callback_query).await;
+                }
+            }
+        }
+    }
+
+    /// Whether the sender of `message` has at least the role `required` in the chat.
+    async fn has_role(&self, message: &Message, required: Role) -> bool {
+        // Anonymous administrators send messages in the name of the chat itself.
+        if let Some(sender_chat) = &message.sender_chat {
+            if sender_chat.id == message.chat.id {
+                return self.config.chat_admin_role >= required;
+            }
+        }
+        self.user_has_role(message.chat.id, message.from.as_ref(), required)
+            .await
+    }
+
+    async fn user_has_role(&self, chat_id: i64, user: Option<&User>, required: Role) -> bool {
+        let user = match user {
+            Some(user) => user,
+            None => return self.config.default_role >= required,
+        };
+        let role = self
+            .config
+            .user_roles
+            .get(&user.id.to_string())
+            .copied()
+            .unwrap_or(self.config.default_role);
+        if role >= required {
+            return true;
+        }
+        if self.config.chat_admin_role < required {
+            return false;
+        }
+
+        match self.chat_admins(chat_id).await {
+            Some(admins) => admins.contains(&user.id),
+            None => false,
+        }
+    }
+
+    /// The user ids of the administrators of the chat. They are cached, since the role of a user
+    /// may be checked for every message relayed by a chat bridge.
+    async fn chat_admins(&self, chat_id: i64) -> Option<HashSet<u64>> {
+        // Private chats have no administrators.
+        if chat_id > 0 {
+            return None;
+        }
+        if let Some((requested, admins)) = self.chat_admins.lock().await.get(&chat_id) {
+            if requested.elapsed() < CHAT_ADMINS_CACHE_TTL {
+                return Some(admins.clone());
             }
         }
+
+        let params = GetChatAdministratorsParams::builder()
+            .chat_id(chat_id)
+            .build();
+        let admins = match self.api.get_chat_administrators(&params).await {
+            Ok(response) => response
+                .result
+                .iter()
+                .filter_map(|member| match member {
+                    ChatMember::Owner(owner) => Some(owner.user.id),
+                    ChatMember::Administrator(admin) => Some(admin.user.id),
+                    _ => None,
+                })
+                .collect::<HashSet<u64>>(),
+            Err(err) => {
+                BotError::from(err).log("get_chat_administrators", Some(chat_id));
+                return None;
+            }
+        };
+        self.chat_admins
+            .lock()
+            .await
+            .insert(chat_id, (Instant::now(), admins.clone()));
+        Some(admins)
     }
 
-    async fn start_server_handler(&self, message: Message) {
+    async fn start_server_handler(&self, message: Message) -> Result<(), BotError> {
         let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
-        match self.get_service_active(&message) {
+        match self.get_service_active(&message).await? {
             Inactive => {
-                let inline_keyboard = InlineKeyboardMarkup::builder()
-                    .inline_keyboard(vec![vec![InlineKeyboardButton::builder()
-                        .text(LOCALES.lookup(&self.locale, "activate-chatbridge-inline"))
-                        .callback_data("inline_enable_chatbridge")
-                        .build()]])
-                    .build();
                 let send_message_params = SendMessageParams::builder()
                     .chat_id(message.chat.id)
-                    .text(LOCALES.lookup(&self.locale, "start-server"))
+                    .text(self.server_text(server_name, "start-server", HashMap::new()))
                     .reply_to_message_id(message.message_id)
-                    .reply_markup(ReplyMarkup::InlineKeyboardMarkup(inline_keyboard))
+                    .reply_markup(ReplyMarkup::InlineKeyboardMarkup(
+                        self.start_message_keyboard(true),
+                    ))
                     .build();
 
-                let start_message = match self.api.send_message(&send_message_params) {
+                let start_message = match self.api.send_message(&send_message_params).await {
                     Ok(message) => Some(message.result),
                     Err(err) => {
-                        println!("Failed to send message: {:?}", err);
+                        BotError::from(err).log("start_server", Some(message.chat.id));
                         None
                     }
                 };
 
-                println!("Start server {:}.", server_name);
-                let service_name = format!("minecraft-server@{:}.service", server_name);
-                Command::new("sudo")
-                    .args(["systemctl", "start", &service_name])
-                    .spawn()
-                    .expect("Error executing command");
+                let log = self.start_server_with_log(server_name).await?;
 
-                let mut bot_data = self.clone();
-                let message_clone = message.clone();
-                let server_name_clone = String::from(server_name);
-
-                let (tx, rx) = mpsc::channel();
-
-                let handle = tokio::spawn(async move {
-                    println!(
-                        "Start thread to check online status of {:}.",
-                        server_name_clone
-                    );
-                    let out = AsyncCommand::new("sudo")
-                        .args(["journalctl", "-f", "-u", &service_name])
-                        .stdout(Stdio::piped())
-                        .spawn()
-                        .unwrap();
-                    let mut reader = BufReader::new(out.stdout.unwrap()).lines();
-                    while let Some(line) = reader.next().await {
-                        if line.unwrap().contains("]: Done") {
-                            println!("Server {} started.", server_name_clone);
-                            if let Some(start_message) = start_message {
-                                let inline_keyboard = InlineKeyboardMarkup::builder()
-                                    .inline_keyboard(vec![vec![]])
-                                    .build();
-                                let edit_message_params =
-                                    EditMessageTextParams::builder()
-                                        .chat_id(start_message.chat.id)
-                                        .message_id(start_message.message_id)
-                                        .reply_markup(inline_keyboard)
-                                        .text(&LOCALES.lookup(
-                                            &bot_data.locale,
-                                            "start-server-without-button",
-                                        ))
-                                        .build();
-                                if let Err(err) =
-                                    bot_data.api.edit_message_text(&edit_message_params)
-                                {
-                                    println!("Failed to edit message: {:?}", err);
-                                }
-                            }
-
-                            bot_data
-                                .send_message_with_reply(
-                                    &message_clone,
-                                    &LOCALES.lookup(&bot_data.locale, "server-started-now"),
-                                )
-                                .await;
-                            match tx.send("finished") {
-                                Ok(_) => {}
-                                Err(_) => {
-                                    println!("Main thread of starting server finished before this. Should not be reached.")
-                                }
-                            }
-                            break;
-                        }
-                    }
-
-                    let message_chatbridge_option = bot_data
-                        .enable_chatbridge_after_start_map
-                        .write()
-                        .await
-                        .remove(&message_clone.chat.id.to_string());
-
-                    if let Some(message_chatbridge) = message_chatbridge_option {
-                        println!("Start thread to enable chatbridge handler from start_server.");
-                        tokio::spawn(async move {
-                            bot_data.enable_chatbridge_handler(message_chatbridge).await;
-                        });
-                    }
-
-                    println!(
-                        "Finished thread to check online status of {:}.",
-                        server_name_clone
-                    );
+                let bot_data = self.clone();
+                let server_name = String::from(server_name);
+                tokio::spawn(async move {
+                    bot_data
+                        .watch_start(server_name, message, start_message, log)
+                        .await;
                 });
-
-                let mut server_done = false;
-                for _ in 0..60 {
-                    sleep(Duration::from_secs(1)).await;
-                    if let Ok("finished") = rx.try_recv() {
-                        server_done = true;
-                        break;
-                    }
-                }
-                if !server_done {
-                    handle.abort();
-                    self.send_message_with_reply(
-                        &message,
-                        &LOCALES.lookup(&self.locale, "server-started-unknown"),
-                    )
-                    .await;
-                }
-                println!(
-                    "Finishing handling of start_server. Server {} was started properly: {}",
-                    server_name, server_done
-                );
             }
             Starting => {
                 println!("Server {:} already starting.", server_name);
@@ -353,16 +658,252 @@ impl BotData {
                 .await;
             }
         }
+        Ok(())
+    }
+
+    /// Starts the server and returns its log, if it can be followed.
+    async fn start_server_with_log(
+        &self,
+        server_name: &str,
+    ) -> Result<Option<mpsc::Receiver<String>>, BotError> {
+        // Follow the log before starting, so that no line is missed.
+        let log = match self.servers[server_name].log_source.follow().await {
+            Ok(log) => Some(log),
+            Err(err) => {
+                println!("Failed to follow log of server {:}: {}", server_name, err);
+                None
+            }
+        };
+        self.start_server(server_name).await?;
+        Ok(log)
+    }
+
+    async fn start_server(&self, server_name: &str) -> Result<(), BotError> {
+        println!("Start server {:}.", server_name);
+        self.servers[server_name].service_manager.start().await?;
+        Ok(())
+    }
+
+    /// Follows the start of the server until it is ready or has failed and tells the chat about it.
+    /// If the start takes longer than the start timeout of the server, the chat is told in between.
+    async fn watch_start(
+        &self,
+        server_name: String,
+        message: Message,
+        start_message: Option<Message>,
+        mut log: Option<mpsc::Receiver<String>>,
+    ) {
+        println!("Watching start of server {:}.", server_name);
+        let start_timeout = sleep(Duration::from_secs(
+            self.config.servers[&server_name].start_timeout,
+        ));
+        tokio::pin!(start_timeout);
+        let mut timed_out = false;
+        let mut log_open = log.is_some();
+        let mut check = interval(START_CHECK_INTERVAL);
+        let parser = EventParser::new();
+        let mut progress = StartupProgress::default();
+        let mut report = FailureReport::default();
+
+        loop {
+            tokio::select! {
+                line = async { log.as_mut()?.recv().await }, if log_open => match line {
+                    Some(line) => {
+                        let event = parser.parse(&line);
+                        report.record(&line, event.as_ref());
+                        match event {
+                            Some(ServerEvent::Progress(phase)) => {
+                                if let (Some(start_message), Some(status)) =
+                                    (&start_message, progress.update(phase, &self.locale))
+                                {
+                                    self.edit_start_message(start_message, &status, false)
+                                        .await;
+                                }
+                            }
+                            Some(ServerEvent::Done(startup_time)) => {
+                                println!(
+                                    "Server {} started in {:.3}s.",
+                                    server_name,
+                                    startup_time.as_secs_f64()
+                                );
+                                self.report_started(
+                                    &message,
+                                    start_message.as_ref(),
+                                    Some(startup_time),
+                                    timed_out,
+                                )
+                                .await;
+                                break;
+                            }
+                            _ => {}
+                        }
+                    }
+                    None => {
+                        println!("Log of server {} ended while starting.", server_name);
+                        log_open = false;
+                    }
+                },
+                _ = &mut start_timeout, if !timed_out => {
+                    println!("Server {} is not started yet, still watching.", server_name);
+                    timed_out = true;
+                    self.send_message_with_reply(
+                        &message,
+                        &LOCALES.lookup(&self.locale, "server-starting-slowly"),
+                    )
+                    .await;
+                }
+                _ = check.tick() => {
+                    match self.servers[&server_name].service_manager.exit_status().await {
+                        Ok(Some(exit_status)) => {
+                            println!("Server {} failed to start: {}", server_name, exit_status);
+                            self.report_start_failure(
+                                &message,
+                                start_message.as_ref(),
+                                &exit_status,
+                                &report,
+                            )
+                            .await;
+                            break;
+                        }
+                        Ok(None) => {}
+                        Err(err) => BotError::from(err).log("watch_start", Some(message.chat.id)),
+                    }
+                    // Without the log, only RCON tells when the server is ready. After the start
+                    // timeout, the log might just lack the line this bot is waiting for.
+                    if !log_open || timed_out {
+                        if let Ok(Running { .. }) = self.get_server_status(&server_name).await {
+                            self.report_started(&message, start_message.as_ref(), None, timed_out)
+                                .await;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.activate_pending_chatbridges(&server_name).await;
+        println!("Finished watching start of server {:}.", server_name);
+    }
+
+    async fn report_started(
+        &self,
+        message: &Message,
+        start_message: Option<&Message>,
+        startup_time: Option<Duration>,
+        late: bool,
+    ) {
+        if let Some(start_message) = start_message {
+            let status = match startup_time {
+                Some(startup_time) => done_text(startup_time, &self.locale),
+                None => LOCALES.lookup(&self.locale, "startup-ready"),
+            };
+            self.edit_start_message(start_message, &status, true).await;
+        }
+        let text_id = if late {
+            "server-started-late"
+        } else {
+            "server-started-now"
+        };
+        let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+        self.send_message_with_reply(
+            message,
+            &self.server_text(server_name, text_id, HashMap::new()),
+        )
+        .await;
+    }
+
+    async fn report_start_failure(
+        &self,
+        message: &Message,
+        start_message: Option<&Message>,
+        exit_status: &str,
+        report: &FailureReport,
+    ) {
+        if let Some(start_message) = start_message {
+            self.edit_start_message(
+                start_message,
+                &LOCALES.lookup(&self.locale, "startup-failed"),
+                true,
+            )
+            .await;
+        }
+        let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+        let headline = self.server_text(server_name, "server-start-failed", {
+            let mut map = HashMap::new();
+            map.insert(
+                String::from("status"),
+                FluentValue::String(Cow::from(exit_status)),
+            );
+            map
+        });
+        let (text, entities) = report.format(&headline, &self.locale);
+        let send_message_params = SendMessageParams::builder()
+            .chat_id(message.chat.id)
+            .text(text)
+            .entities(entities)
+            .reply_to_message_id(message.message_id)
+            .build();
+        self.outbox
+            .send(message.chat.id, send_message_params, false);
+    }
+
+    /// The button to activate the chat bridge beneath the start message, if `offer_chatbridge`.
+    fn start_message_keyboard(&self, offer_chatbridge: bool) -> InlineKeyboardMarkup {
+        let buttons = if offer_chatbridge {
+            vec![InlineKeyboardButton::builder()
+                .text(LOCALES.lookup(&self.locale, "activate-chatbridge-inline"))
+                .callback_data("inline_enable_chatbridge")
+                .build()]
+        } else {
+            vec![]
+        };
+        InlineKeyboardMarkup::builder()
+            .inline_keyboard(vec![buttons])
+            .build()
+    }
+
+    /// Shows `status` beneath the start message. The button to activate the chat bridge is kept
+    /// until the server is `done` or the chat bridge is requested.
+    async fn edit_start_message(&self, start_message: &Message, status: &str, done: bool) {
+        let chat_id = start_message.chat.id.to_string();
+        let offer_chatbridge = !done
+            && !self.chatbridge_map.read().await.contains_key(&chat_id)
+            && !self
+                .enable_chatbridge_after_start_map
+                .read()
+                .await
+                .contains_key(&chat_id);
+        let text_id = if offer_chatbridge {
+            "start-server"
+        } else {
+            "start-server-without-button"
+        };
+        let edit_message_params = EditMessageTextParams::builder()
+            .chat_id(start_message.chat.id)
+            .message_id(start_message.message_id)
+            .reply_markup(self.start_message_keyboard(offer_chatbridge))
+            .text(start_message_text(
+                &self.server_text(
+                    &self.config.chat_server_map[&chat_id],
+                    text_id,
+                    HashMap::new(),
+                ),
+                status,
+            ))
+            .build();
+        if let Err(err) = self.api.edit_message_text(&edit_message_params).await {
+            BotError::from(err).log("start_server", Some(start_message.chat.id));
+        }
     }
 
-    async fn stop_server_handler(&mut self, message: Message) {
+    async fn stop_server_handler(&mut self, message: Message) -> Result<(), BotError> {
         let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
 
-        match self.get_service_active(&message) {
+        match self.get_service_active(&message).await? {
             Inactive => {
                 self.send_message_with_reply(
                     &message,
-                    &LOCALES.lookup(&self.locale, "server-not-running"),
+                    &self.server_text(server_name, "server-not-running", HashMap::new()),
                 )
                 .await;
                 println!("Server {:} not running, cannot stop.", server_name);
@@ -378,32 +919,150 @@ impl BotData {
             ServerStatus::Running { .. } => {
                 self.send_message_with_reply(
                     &message,
-                    &LOCALES.lookup(&self.locale, "stop-server"),
+                    &self.server_text(server_name, "stop-server", HashMap::new()),
                 )
                 .await;
-                println!("Stop server {:}.", server_name);
-                self.disable_chatbridge_handler(message.clone(), false)
-                    .await;
-                let server_name =
-                    self.config.chat_server_map[&message.chat.id.to_string()].as_str();
-                Command::new("sudo")
-                    .args([
-                        "systemctl",
-                        "stop",
-                        format!("minecraft-server@{:}.service", server_name).as_str(),
-                    ])
-                    .spawn()
-                    .expect("Error executing command");
-            }
+                self.stop_server(server_name, &[message.chat.id]).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops the server. The chat bridges of `chat_ids` are removed before, so that they do not
+    /// report the stop as done by someone else.
+    async fn stop_server(&self, server_name: &str, chat_ids: &[i64]) -> Result<(), BotError> {
+        println!("Stop server {:}.", server_name);
+        for chat_id in chat_ids {
+            self.remove_chatbridge(*chat_id).await;
+        }
+        self.expect_stop(server_name).await;
+        self.servers[server_name].service_manager.stop().await?;
+        Ok(())
+    }
+
+    /// Stops the servers that cannot keep running once the bot exits.
+    async fn shutdown_servers(&self) {
+        for (server_name, server) in self.servers.iter() {
+            self.expect_stop(server_name).await;
+            if let Err(err) = server.service_manager.shutdown().await {
+                BotError::from(err).log("shutdown", None);
+            }
+        }
+    }
+
+    async fn keep_alive_handler(&self, message: Message, hours: Option<u32>) {
+        let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+        if self.config.servers[server_name].idle_shutdown.is_none() {
+            self.send_message_with_reply(
+                &message,
+                &LOCALES.lookup(&self.locale, "keep-alive-not-needed"),
+            )
+            .await;
+            return;
+        }
+        let hours = hours.unwrap_or(KEEP_ALIVE_DEFAULT_HOURS);
+        if hours == 0 {
+            println!("Idle shutdown of server {} resumed.", server_name);
+            self.keep_alive.lock().await.remove(server_name);
+            self.send_message_with_reply(
+                &message,
+                &LOCALES.lookup(&self.locale, "keep-alive-ended"),
+            )
+            .await;
+            return;
+        }
+        let hours = hours.min(KEEP_ALIVE_MAX_HOURS);
+        println!(
+            "Idle shutdown of server {} suspended for {} hours.",
+            server_name, hours
+        );
+        self.keep_alive.lock().await.insert(
+            String::from(server_name),
+            Instant::now() + Duration::from_secs(u64::from(hours) * 3600),
+        );
+        let reply = LOCALES.lookup_with_args(&self.locale, "keep-alive", &{
+            let mut map = HashMap::new();
+            map.insert(String::from("hours"), FluentValue::from(hours));
+            map
+        });
+        self.send_message_with_reply(&message, &reply).await;
+    }
+
+    /// Whether the idle shutdown of the server is suspended via `/keep_alive`.
+    async fn is_kept_alive(&self, server_name: &str) -> bool {
+        let mut keep_alive = self.keep_alive.lock().await;
+        match keep_alive.get(server_name) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                keep_alive.remove(server_name);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Looks up a text that names the server as `$server`, besides the given `args`.
+    fn server_text(
+        &self,
+        server_name: &str,
+        text_id: &str,
+        mut args: HashMap<String, FluentValue>,
+    ) -> String {
+        args.insert(
+            String::from("server"),
+            FluentValue::String(Cow::from(String::from(
+                self.config.servers[server_name].name(server_name),
+            ))),
+        );
+        LOCALES.lookup_with_args(&self.locale, text_id, &args)
+    }
+
+    /// The chats linked to the server.
+    fn chats_of_server(&self, server_name: &str) -> Vec<i64> {
+        self.config
+            .chat_server_map
+            .iter()
+            .filter(|(_, chat_server)| chat_server.as_str() == server_name)
+            .filter_map(|(chat_id, _)| chat_id.parse().ok())
+            .collect()
+    }
+
+    async fn schedule_handler(&self, message: Message) -> Result<(), BotError> {
+        let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+        let time_zone = self.config.time_zone()?;
+        let now = Utc::now().with_timezone(&time_zone);
+        let runs = upcoming_runs(&self.config.servers[server_name], now)?;
+        if runs.is_empty() {
+            self.send_message_with_reply(&message, &LOCALES.lookup(&self.locale, "schedule-none"))
+                .await;
+            return Ok(());
+        }
+        let mut text = LOCALES.lookup_with_args(&self.locale, "schedule-upcoming", &{
+            let mut map = HashMap::new();
+            map.insert(
+                String::from("timeZone"),
+                FluentValue::String(Cow::from(time_zone.name())),
+            );
+            map
+        });
+        for (time, action) in runs {
+            text.push_str(&format!(
+                "\n{}  {}",
+                time.format("%Y-%m-%d %H:%M"),
+                action_text(action, &self.locale)
+            ));
         }
+        self.send_message_with_reply(&message, &text).await;
+        Ok(())
     }
 
-    async fn status_server_handler(&self, message: Message) {
-        match self.get_service_active(&message) {
+    async fn status_server_handler(&self, message: Message) -> Result<(), BotError> {
+        let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+        match self.get_service_active(&message).await? {
             Inactive => {
                 self.send_message_with_reply(
                     &message,
-                    &LOCALES.lookup(&self.locale, "server-not-running"),
+                    &self.server_text(server_name, "server-not-running", HashMap::new()),
                 )
                 .await;
             }
@@ -414,28 +1073,33 @@ impl BotData {
                 )
                 .await;
             }
+            ServerStatus::Running { players: None } => {
+                self.send_message_with_reply(
+                    &message,
+                    &self.server_text(
+                        server_name,
+                        "server-running-unknown-players",
+                        HashMap::new(),
+                    ),
+                )
+                .await;
+            }
             ServerStatus::Running {
-                current_players,
-                max_players,
-                players,
+                players: Some(player_list),
             } => {
-                let reply = LOCALES.lookup_with_args(&self.locale, "server-running", &{
+                let reply = self.server_text(server_name, "server-running", {
                     let mut map = HashMap::new();
                     map.insert(
                         String::from("currentPlayers"),
-                        FluentValue::Number(FluentNumber::from(
-                            u16::from_str(&current_players).unwrap(),
-                        )),
+                        FluentValue::from(player_list.online),
                     );
                     map.insert(
                         String::from("maxPlayers"),
-                        FluentValue::Number(FluentNumber::from(
-                            u16::from_str(&max_players).unwrap(),
-                        )),
+                        FluentValue::from(player_list.max),
                     );
                     map.insert(
                         String::from("players"),
-                        FluentValue::String(Cow::from(players)),
+                        FluentValue::String(Cow::from(player_list.names.join(", "))),
                     );
                     map
                 });
@@ -443,36 +1107,30 @@ impl BotData {
                 self.send_message_with_reply(&message, &reply).await;
             }
         }
+        Ok(())
     }
 
     async fn enable_chatbridge_inline_handler(&mut self, callback_query: CallbackQuery) {
         if let Some(message) = callback_query.message {
-            {
-                let inline_keyboard = InlineKeyboardMarkup::builder()
-                    .inline_keyboard(vec![vec![]])
-                    .build();
-                let edit_message_params = EditMessageTextParams::builder()
-                    .chat_id(message.chat.id)
-                    .message_id(message.message_id)
-                    .reply_markup(inline_keyboard)
-                    .text(&LOCALES.lookup(&self.locale, "start-server-without-button"))
-                    .build();
-                if let Err(err) = self.api.edit_message_text(&edit_message_params) {
-                    println!("Failed to edit message: {:?}", err);
-                }
+            // The button is not offered again, whether the chat bridge can be activated or not.
+            self.edit_start_message(&message, shown_status(&message), true)
+                .await;
+            let chat_id = message.chat.id;
+            if let Err(err) = self.enable_chatbridge_handler(message.clone()).await {
+                self.error_handler(&message, "enable_chatbridge_inline", err)
+                    .await;
             }
-            self.enable_chatbridge_handler(message).await;
 
             let answer_callback_query = AnswerCallbackQueryParams::builder()
                 .callback_query_id(&callback_query.id)
                 .build();
-            if let Err(err) = self.api.answer_callback_query(&answer_callback_query) {
-                println!("Failed to send answer_callback_reply: {:?}", err);
+            if let Err(err) = self.api.answer_callback_query(&answer_callback_query).await {
+                BotError::from(err).log("answer_callback_query", Some(chat_id));
             }
         }
     }
 
-    async fn enable_chatbridge_handler(&mut self, message: Message) {
+    async fn enable_chatbridge_handler(&mut self, message: Message) -> Result<(), BotError> {
         if self
             .chatbridge_map
             .read()
@@ -489,7 +1147,7 @@ impl BotData {
             )
             .await;
         } else {
-            match self.get_service_active(&message) {
+            match self.get_service_active(&message).await? {
                 Inactive => {
                     self.send_message_with_reply(
                         &message,
@@ -521,6 +1179,7 @@ impl BotData {
                             .write()
                             .await
                             .insert(message.chat.id.to_string(), message);
+                        self.save_state().await;
                     } else {
                         self.send_message_with_reply(
                             &message,
@@ -534,72 +1193,21 @@ impl BotData {
                     }
                 }
                 ServerStatus::Running { .. } => {
+                    println!(
+                        "Chat bridge will be activated for {}.",
+                        &message.chat.id.to_string()
+                    );
+                    self.start_chatbridge(message.chat.id).await?;
                     self.send_message_with_reply(
                         &message,
                         &LOCALES.lookup(&self.locale, "activate-chatbridge"),
                     )
                     .await;
-                    println!(
-                        "Chat bridge will be activated for {}.",
-                        &message.chat.id.to_string()
-                    );
-                    let message_clone = message.clone();
-                    let mut bot_data = self.clone();
-                    let handle = tokio::spawn(async move {
-                        let message = message_clone;
-                        println!(
-                            "Start chatbridge thread for {}.",
-                            &message.chat.id.to_string()
-                        );
-                        let service_name = format!(
-                            "minecraft-server@{:}.service",
-                            bot_data.config.chat_server_map[&message.chat.id.to_string()].as_str()
-                        );
-                        let message_regex = Regex::new(r"^[[:alpha:]]{3} \d{2} \d{2}:\d{2}:\d{2} [A-Za-z0-9\-]* start\.sh\[\d*\]: \[\d{2}:\d{2}:\d{2} INFO\]: <([A-Za-z0-9]*)> (.*)$").unwrap();
-                        let stop_regex = Regex::new(r"^[[:alpha:]]{3} \d{2} \d{2}:\d{2}:\d{2} [A-Za-z0-9\-]* systemd\[1\]: minecraft-server@.*?\.service: Deactivated successfully\.$").unwrap();
-                        let out = AsyncCommand::new("sudo")
-                            .args(["journalctl", "-f", "-n", "0", "-u", &service_name])
-                            .stdout(Stdio::piped())
-                            .spawn()
-                            .unwrap();
-                        let mut reader = BufReader::new(out.stdout.unwrap()).lines();
-                        while let Some(line) = reader.next().await {
-                            if let Some(captures) = message_regex.captures(line.as_ref().unwrap()) {
-                                let send_message_params = SendMessageParams::builder()
-                                    .chat_id(message.chat.id)
-                                    .text(format!("{}: {}", &captures[1], &captures[2]))
-                                    .entities(vec![MessageEntity::builder()
-                                        .type_field(Bold)
-                                        .offset(0_u16)
-                                        .length(captures[1].len() as u16)
-                                        .build()])
-                                    .build();
-
-                                if let Err(err) = bot_data.api.send_message(&send_message_params) {
-                                    println!("Failed to send message: {:?}", err);
-                                }
-                            } else if stop_regex.is_match(line.as_ref().unwrap()) {
-                                tokio::spawn(async move {
-                                    bot_data
-                                        .send_message(
-                                            message.chat.id,
-                                            &LOCALES.lookup(
-                                                &bot_data.locale,
-                                                "server-stopped-externally",
-                                            ),
-                                        )
-                                        .await;
-                                    bot_data.disable_chatbridge_handler(message, false).await;
-                                });
-                                return;
-                            }
-                        }
-                    });
-                    let mut chatbridge_lock = self.chatbridge_map.write().await;
-                    chatbridge_lock.insert(message.chat.id.to_string(), handle);
+                    self.save_state().await;
                 }
             }
         }
+        Ok(())
     }
 
     async fn disable_chatbridge_handler(&mut self, message: Message, send_message: bool) {
@@ -621,23 +1229,387 @@ impl BotData {
                 .await;
             }
         } else {
-            let mut chatbridge_lock = self.chatbridge_map.write().await;
-            if chatbridge_lock.contains_key(&message.chat.id.to_string()) {
-                if send_message {
-                    self.send_message_with_reply(
-                        &message,
-                        &LOCALES.lookup(&self.locale, "deactivate-chatbridge"),
-                    )
+            if send_message {
+                self.send_message_with_reply(
+                    &message,
+                    &LOCALES.lookup(&self.locale, "deactivate-chatbridge"),
+                )
+                .await;
+            }
+            self.remove_chatbridge(message.chat.id).await;
+        }
+    }
+
+    /// Starts relaying the log of the server of the chat to the chat.
+    ///
+    /// The log is followed before the chat bridge counts as active, so that a log that cannot be
+    /// read does not leave a chat bridge behind that relays nothing.
+    async fn start_chatbridge(&self, chat_id: i64) -> Result<(), BotError> {
+        let server_name = self.config.chat_server_map[&chat_id.to_string()].as_str();
+        let mut log = self.servers[server_name]
+            .log_source
+            .follow()
+            .await
+            .map_err(BotError::Log)?;
+        let bot_data = self.clone();
+        let handle = tokio::spawn(async move {
+            println!("Start chatbridge thread for {}.", chat_id);
+            let parser = EventParser::new();
+            while let Some(line) = log.recv().await {
+                match parser.parse(&line) {
+                    Some(ServerEvent::Chat {
+                        player,
+                        message: text,
+                    }) => {
+                        bot_data
+                            .send_chatbridge_message(chat_id, &player, ": ", &text)
+                            .await;
+                    }
+                    Some(ServerEvent::Emote { player, action }) => {
+                        bot_data
+                            .send_chatbridge_message(
+                                chat_id,
+                                &format!("* {}", player),
+                                " ",
+                                &action,
+                            )
+                            .await;
+                    }
+                    Some(ServerEvent::Say {
+                        sender,
+                        message: text,
+                    }) => {
+                        bot_data
+                            .send_chatbridge_message(chat_id, &format!("[{}]", sender), " ", &text)
+                            .await;
+                    }
+                    Some(ServerEvent::Join { player }) => {
+                        bot_data
+                            .send_join_leave_message(chat_id, "player-joined", &player)
+                            .await;
+                    }
+                    Some(ServerEvent::Leave { player }) => {
+                        bot_data
+                            .send_join_leave_message(chat_id, "player-left", &player)
+                            .await;
+                    }
+                    Some(ServerEvent::Death {
+                        player,
+                        message: text,
+                    }) => {
+                        bot_data.send_death_message(chat_id, &player, &text).await;
+                    }
+                    Some(ServerEvent::Advancement {
+                        player,
+                        kind,
+                        title,
+                    }) => {
+                        bot_data
+                            .send_advancement_message(chat_id, &player, kind, &title)
+                            .await;
+                    }
+                    Some(ServerEvent::Stopping) => {
+                        tokio::spawn(async move {
+                            bot_data
+                                .send_message(
+                                    chat_id,
+                                    &LOCALES.lookup(&bot_data.locale, "server-stopped-externally"),
+                                )
+                                .await;
+                            bot_data.remove_chatbridge(chat_id).await;
+                        });
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        });
+        self.chatbridge_map
+            .write()
+            .await
+            .insert(chat_id.to_string(), handle);
+        Ok(())
+    }
+
+    /// Stops the chat bridge of the chat, if it is active.
+    async fn remove_chatbridge(&self, chat_id: i64) {
+        let handle = self
+            .chatbridge_map
+            .write()
+            .await
+            .remove(&chat_id.to_string());
+        if let Some(handle) = handle {
+            println!("Chat bridge for {} gets deactivated.", chat_id);
+            handle.abort();
+            self.save_state().await;
+        }
+    }
+
+    /// Stops all chat bridges when the bot shuts down.
+    ///
+    /// The chat bridges stay in the saved state, so that they are restored on the next start.
+    async fn pause_chatbridges(&self) {
+        let mut chat_ids = Vec::new();
+        for (chat_id, handle) in self.chatbridge_map.write().await.iter_mut() {
+            println!("Chat bridge for {} gets paused.", chat_id);
+            handle.abort();
+            // Dropping the log of the chat bridge stops and reaps the process following it.
+            let _ = handle.await;
+            chat_ids.extend(chat_id.parse::<i64>());
+        }
+        for chat_id in chat_ids {
+            if self.config.chat_settings(chat_id).shutdown_message {
+                self.send_message(chat_id, &LOCALES.lookup(&self.locale, "bot-shutdown"))
                     .await;
+            }
+        }
+    }
+
+    /// Enables the chat bridges that were requested while the server was starting.
+    async fn activate_pending_chatbridges(&self, server_name: &str) {
+        let messages = {
+            let mut pending = self.enable_chatbridge_after_start_map.write().await;
+            let chat_ids = pending
+                .keys()
+                .filter(|chat_id| {
+                    self.config
+                        .chat_server_map
+                        .get(*chat_id)
+                        .map(String::as_str)
+                        == Some(server_name)
+                })
+                .cloned()
+                .collect::<Vec<String>>();
+            chat_ids
+                .iter()
+                .filter_map(|chat_id| pending.remove(chat_id))
+                .collect::<Vec<Message>>()
+        };
+        if messages.is_empty() {
+            return;
+        }
+        self.save_state().await;
+        for message in messages {
+            println!(
+                "Start thread to enable chat bridge for {} after start of {}.",
+                message.chat.id, server_name
+            );
+            let mut bot_data = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = bot_data.enable_chatbridge_handler(message.clone()).await {
+                    bot_data
+                        .error_handler(&message, "activate_pending_chatbridges", err)
+                        .await;
                 }
+            });
+        }
+    }
+
+    /// Makes the active chat bridges of the server pending, so that they are activated again once
+    /// the server is started again, e.g. by a scheduled restart.
+    async fn reactivate_chatbridges_after_start(&self, server_name: &str) {
+        let chat_ids = {
+            let chatbridges = self.chatbridge_map.read().await;
+            self.chats_of_server(server_name)
+                .into_iter()
+                .filter(|chat_id| chatbridges.contains_key(&chat_id.to_string()))
+                .collect::<Vec<i64>>()
+        };
+        if chat_ids.is_empty() {
+            return;
+        }
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or(0);
+        {
+            let mut pending = self.enable_chatbridge_after_start_map.write().await;
+            for chat_id in chat_ids {
                 println!(
-                    "Chat bridge for {} gets deactivated.",
-                    &message.chat.id.to_string()
+                    "Chat bridge for {} is activated again after the start of {}.",
+                    chat_id, server_name
                 );
-                chatbridge_lock[&message.chat.id.to_string()].abort();
-                chatbridge_lock.remove(&message.chat.id.to_string());
+                // There is no command to reply to, the replies are sent without one.
+                let message = Message::builder()
+                    .message_id(0)
+                    .date(date)
+                    .chat(
+                        Chat::builder()
+                            .id(chat_id)
+                            .type_field(ChatType::Group)
+                            .build(),
+                    )
+                    .build();
+                pending.insert(chat_id.to_string(), message);
+            }
+        }
+        self.save_state().await;
+    }
+
+    /// Waits until the server is started and enables the pending chat bridges then.
+    async fn watch_for_start(&self, server_name: &str) {
+        let mut log = match self.servers[server_name].log_source.follow().await {
+            Ok(log) => log,
+            Err(err) => {
+                println!("Failed to follow log of server {:}: {}", server_name, err);
+                return;
+            }
+        };
+        let bot_data = self.clone();
+        let server_name = String::from(server_name);
+        tokio::spawn(async move {
+            let parser = EventParser::new();
+            while let Some(line) = log.recv().await {
+                if let Some(ServerEvent::Done(_)) = parser.parse(&line) {
+                    println!("Server {} started.", server_name);
+                    bot_data.activate_pending_chatbridges(&server_name).await;
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Starts a chat bridge saved before the last shutdown of the bot. If that fails, the chat is
+    /// told, since nobody asked for it in this run of the bot.
+    async fn restore_chatbridge(&self, chat_id: i64) {
+        println!("Restoring chat bridge for {}.", chat_id);
+        if let Err(err) = self.start_chatbridge(chat_id).await {
+            err.log("restore_state", Some(chat_id));
+            self.send_message(chat_id, &LOCALES.lookup(&self.locale, err.text_id()))
+                .await;
+        }
+    }
+
+    /// Restores the chat bridges saved before the last shutdown of the bot.
+    ///
+    /// Chat bridges of servers that have been stopped in the meantime are dropped.
+    async fn restore_state(&self, state: BotState) {
+        let mut starting_servers = HashSet::new();
+        for chat_id in state.chatbridges {
+            let server_name = match self.config.chat_server_map.get(&chat_id.to_string()) {
+                Some(server_name) => server_name,
+                None => continue,
+            };
+            match self.get_server_status(server_name).await {
+                Ok(Inactive) => println!(
+                    "Server {} is not running anymore, dropping chat bridge for {}.",
+                    server_name, chat_id
+                ),
+                // The log of a starting server can be followed already.
+                Ok(Starting | Running { .. }) => self.restore_chatbridge(chat_id).await,
+                // Better a chat bridge too many than losing one.
+                Err(err) => {
+                    err.log("restore_state", Some(chat_id));
+                    self.restore_chatbridge(chat_id).await;
+                }
+            }
+        }
+        for message in state.pending_chatbridges {
+            let server_name = match self
+                .config
+                .chat_server_map
+                .get(&message.chat.id.to_string())
+            {
+                Some(server_name) => server_name,
+                None => continue,
+            };
+            match self.get_server_status(server_name).await {
+                Ok(Inactive) => println!(
+                    "Server {} is not running anymore, dropping pending chat bridge for {}.",
+                    server_name, message.chat.id
+                ),
+                Ok(Starting) => {
+                    println!("Restoring pending chat bridge for {}.", message.chat.id);
+                    self.enable_chatbridge_after_start_map
+                        .write()
+                        .await
+                        .insert(message.chat.id.to_string(), message);
+                    starting_servers.insert(server_name.as_str());
+                }
+                Ok(Running { .. }) => {
+                    let mut bot_data = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = bot_data.enable_chatbridge_handler(message.clone()).await
+                        {
+                            bot_data.error_handler(&message, "restore_state", err).await;
+                        }
+                    });
+                }
+                // Kept pending, the chat bridge is enabled once the server is started.
+                Err(err) => {
+                    err.log("restore_state", Some(message.chat.id));
+                    println!("Restoring pending chat bridge for {}.", message.chat.id);
+                    self.enable_chatbridge_after_start_map
+                        .write()
+                        .await
+                        .insert(message.chat.id.to_string(), message);
+                    starting_servers.insert(server_name.as_str());
+                }
             }
         }
+        for server_name in starting_servers {
+            self.watch_for_start(server_name).await;
+        }
+        self.save_state().await;
+    }
+
+    /// Marks the server as being stopped on purpose.
+    async fn expect_stop(&self, server_name: &str) {
+        self.expected_stops
+            .lock()
+            .await
+            .insert(String::from(server_name));
+    }
+
+    /// Whether the server is marked as being stopped on purpose and the watchdog has not noticed
+    /// the stop yet.
+    async fn is_stop_expected(&self, server_name: &str) -> bool {
+        self.expected_stops.lock().await.contains(server_name)
+    }
+
+    /// Whether the server was marked as being stopped on purpose. The mark is removed.
+    async fn take_expected_stop(&self, server_name: &str) -> bool {
+        self.expected_stops.lock().await.remove(server_name)
+    }
+
+    /// Saves the active and pending chat bridges, so that they can be restored after a restart.
+    async fn save_state(&self) {
+        let mut state = self.state.lock().await;
+        state.chatbridges = self
+            .chatbridge_map
+            .read()
+            .await
+            .keys()
+            .filter_map(|chat_id| chat_id.parse().ok())
+            .collect();
+        state.pending_chatbridges = self
+            .enable_chatbridge_after_start_map
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect();
+        if let Err(err) = state.save(STATE_FILE) {
+            println!("Failed to save state to {}: {}", STATE_FILE, err);
+        }
+    }
+
+    /// Saves the offset of the next update to request.
+    async fn save_update_offset(&self, offset: u32) {
+        let mut state = self.state.lock().await;
+        state.update_offset = Some(offset);
+        if let Err(err) = state.save(STATE_FILE) {
+            println!("Failed to save state to {}: {}", STATE_FILE, err);
+        }
+    }
+
+    /// Whether the message is older than the configured maximum age.
+    fn is_outdated(&self, message: &Message) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or(0);
+        now.saturating_sub(message.date) > self.config.max_update_age
     }
 
     async fn licence_handler(&self, message: Message) {
@@ -645,95 +1617,190 @@ impl BotData {
             .await;
     }
 
+    async fn permission_denied_handler(&self, message: Message) {
+        println!(
+            "Sender of message {} in {} is not allowed to use this command.",
+            message.message_id,
+            &message.chat.id.to_string()
+        );
+        self.send_message_with_reply(&message, &LOCALES.lookup(&self.locale, "permission-denied"))
+            .await;
+    }
+
+    async fn help_handler(&self, message: Message, command: Option<CommandKind>) {
+        let reply = match command {
+            Some(command) => self.command_description(command),
+            None => self.help_text(),
+        };
+        self.send_message_with_reply(&message, &reply).await;
+    }
+
+    async fn command_error_handler(&self, message: Message, err: CommandError) {
+        println!(
+            "Invalid command in chat {}: {:?}",
+            &message.chat.id.to_string(),
+            err
+        );
+        let reply = match err {
+            CommandError::Unknown(command) => format!(
+                "{}\n\n{}",
+                LOCALES.lookup_with_args(&self.locale, "unknown-command", &{
+                    let mut map = HashMap::new();
+                    map.insert(
+                        String::from("command"),
+                        FluentValue::String(Cow::from(command)),
+                    );
+                    map
+                }),
+                self.help_text()
+            ),
+            CommandError::InvalidArgument { command, argument } => format!(
+                "{}\n\n{}",
+                LOCALES.lookup_with_args(&self.locale, "invalid-argument", &{
+                    let mut map = HashMap::new();
+                    map.insert(
+                        String::from("argument"),
+                        FluentValue::String(Cow::from(argument)),
+                    );
+                    map
+                }),
+                self.command_description(command)
+            ),
+            CommandError::TooManyArguments(command) => format!(
+                "{}\n\n{}",
+                LOCALES.lookup(&self.locale, "too-many-arguments"),
+                self.command_description(command)
+            ),
+        };
+        self.send_message_with_reply(&message, &reply).await;
+    }
+
+    fn command_description(&self, command: CommandKind) -> String {
+        format!(
+            "/{} – {}",
+            command.name(),
+            LOCALES.lookup(&self.locale, command.description_id())
+        )
+    }
+
+    fn help_text(&self) -> String {
+        let mut text = LOCALES.lookup(&self.locale, "help");
+        for command in CommandKind::ALL {
+            text.push('\n');
+            text.push_str(&self.command_description(command));
+        }
+        text
+    }
+
     async fn pass_message_to_chatbridge(&mut self, message: Message) {
+        if let Err(err) = self.relay_message(&message).await {
+            self.error_handler(&message, "chatbridge", err).await;
+        }
+    }
+
+    /// Sends the message to the server of the chat, if the chat bridge is active.
+    async fn relay_message(&self, message: &Message) -> Result<(), BotError> {
         if self
             .chatbridge_map
             .read()
             .await
             .contains_key(&message.chat.id.to_string())
+            && self.has_role(message, Role::Member).await
         {
+            // Messages of channels have no sender to show.
+            let from = match &message.from {
+                Some(from) => from,
+                None => return Ok(()),
+            };
+            let media = media_component(message, &self.locale);
+            let (text, entities) = match (&message.text, &message.caption) {
+                (Some(text), _) => (Some(text), &message.entities),
+                (None, Some(caption)) => (Some(caption), &message.caption_entities),
+                (None, None) => (None, &None),
+            };
+            // Service messages, e.g. about new members, are not passed on.
+            if media.is_none() && text.is_none() {
+                return Ok(());
+            }
             println!(
                 "Received message for chatbridge for {}.",
                 &message.chat.id.to_string()
             );
-            let name = if let Some(username) = &message.from.as_ref().unwrap().username {
-                username
-            } else {
-                &message.from.as_ref().unwrap().first_name
-            };
-            let tellraw = json!([
-                "", // Apparently needed, otherwise the whole message is bold.
-                {
-                    "text": name,
-                    "bold": "true"
-                },
-                ": ",
-                message.text.as_ref().unwrap()
-            ]);
-            Command::new("mcrcon")
-                .args([
-                    "-H",
-                    "localhost",
-                    "-P",
-                    "25575",
-                    "-p",
-                    self.config.rcon_password.as_str(),
-                    &format!("tellraw @a {}", tellraw),
-                ])
-                .output()
-                .expect("Error executing command");
-        }
-    }
-
-    fn get_service_active(&self, message: &Message) -> ServerStatus {
+            let mut components = vec![
+                json!(""), // Apparently needed, otherwise the whole message is bold.
+                sender_component(from, &self.locale),
+                json!(": "),
+            ];
+            if let Some(media) = media {
+                components.push(media);
+                if text.is_some() {
+                    components.push(json!(" "));
+                }
+            }
+            if let Some(text) = text {
+                components.extend(text_components(
+                    text,
+                    entities.as_deref().unwrap_or_default(),
+                ));
+            }
+            let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+            self.broadcast(server_name, components).await?;
+        }
+        Ok(())
+    }
+
+    /// Shows the text components to all players on the server.
+    async fn broadcast(&self, server_name: &str, components: Vec<Value>) -> Result<(), BotError> {
+        let tellraw = Value::Array(components);
+        self.servers[server_name]
+            .rcon
+            .execute(&format!("tellraw @a {}", tellraw))
+            .await?;
+        Ok(())
+    }
+
+    async fn get_service_active(&self, message: &Message) -> Result<ServerStatus, BotError> {
         let server_name = self.config.chat_server_map[&message.chat.id.to_string()].as_str();
+        self.get_server_status(server_name).await
+    }
+
+    async fn get_server_status(&self, server_name: &str) -> Result<ServerStatus, BotError> {
         println!("Get status for server {:}.", server_name);
-        let output = Command::new("sudo")
-            .args([
-                "systemctl",
-                "is-active",
-                format!("minecraft-server@{:}.service", server_name).as_str(),
-            ])
-            .output()
-            .expect("Error executing command");
-        if std::str::from_utf8(&output.stdout).expect("Error") == "active\n" {
+        let active = self.servers[server_name]
+            .service_manager
+            .is_active()
+            .await?;
+        Ok(if active {
             println!("Service for {:} is active.", server_name);
-            let output = Command::new("mcrcon")
-                .args([
-                    "-H",
-                    "localhost",
-                    "-P",
-                    "25575",
-                    "-p",
-                    self.config.rcon_password.as_str(),
-                    "list",
-                ])
-                .output()
-                .expect("Error executing command");
-            if std::str::from_utf8(&output.stderr)
-                .expect("Error")
-                .contains("Connection failed")
-            {
-                println!("Server {:} is starting.", server_name);
-                Starting
-            } else {
-                println!("Server {:} is online.", server_name);
-                let text = std::str::from_utf8(&output.stdout).expect("Error");
-                let re = Regex::new(r"[0-9]+").unwrap();
-                let mut text_iter = re.captures_iter(text);
-                let current_players = text_iter.next().unwrap();
-                let max_players = text_iter.next().unwrap();
-                let re: Vec<&str> = text.split(": ").collect();
-                Running {
-                    current_players: String::from(&current_players[0]),
-                    max_players: String::from(&max_players[0]),
-                    players: String::from(&re[1][..re[1].len() - 5]),
+            match self.servers[server_name].rcon.execute("list").await {
+                Err(RconError::ConnectionRefused) => {
+                    println!("Server {:} is starting.", server_name);
+                    Starting
+                }
+                Err(err) => {
+                    println!(
+                        "Server {:} is starting, RCON not available: {}",
+                        server_name, err
+                    );
+                    Starting
+                }
+                Ok(text) => {
+                    println!("Server {:} is online.", server_name);
+                    let players = self.player_list_parser.parse(&text);
+                    if players.is_none() {
+                        BotError::Parse(format!(
+                            "player list of server {}: {:?}",
+                            server_name, text
+                        ))
+                        .log("get_server_status", None);
+                    }
+                    Running { players }
                 }
             }
         } else {
             println!("Service for server {:} is inactive.", server_name);
             Inactive
-        }
+        })
     }
 
     async fn send_message_with_reply(&self, message: &Message, reply: &str) {
@@ -741,11 +1808,100 @@ impl BotData {
             .chat_id(message.chat.id)
             .text(reply)
             .reply_to_message_id(message.message_id)
+            .allow_sending_without_reply(true)
             .build();
 
-        if let Err(err) = self.api.send_message(&send_message_params) {
-            println!("Failed to send message: {:?}", err);
+        self.outbox
+            .send(message.chat.id, send_message_params, false);
+    }
+
+    /// Sends a line of the chat bridge, `bold` is the name of the author and `text` the message as
+    /// written by the server.
+    async fn send_chatbridge_message(&self, chat_id: i64, bold: &str, separator: &str, text: &str) {
+        let mut formatted = FormattedText::default();
+        formatted.push_styled(&strip_formatting(bold), Bold);
+        formatted.push(separator);
+        formatted.append(FormattedText::from_minecraft(text));
+        let (text, entities) = formatted.into_parts();
+        self.send_formatted_message(chat_id, &text, entities, false)
+            .await;
+    }
+
+    async fn send_join_leave_message(&self, chat_id: i64, text_id: &str, player: &str) {
+        let chat_settings = self.config.chat_settings(chat_id);
+        if !chat_settings.join_leave_messages {
+            return;
         }
+        let text = LOCALES.lookup_with_args(&self.locale, text_id, &{
+            let mut map = HashMap::new();
+            map.insert(
+                String::from("player"),
+                FluentValue::String(Cow::from(player)),
+            );
+            map
+        });
+        let entities = entity_for(&text, player, Bold).into_iter().collect();
+        self.send_formatted_message(chat_id, &text, entities, chat_settings.join_leave_silent)
+            .await;
+    }
+
+    async fn send_death_message(&self, chat_id: i64, player: &str, death_message: &str) {
+        // Death messages are relayed as written by the server, there are too many to translate.
+        let text = format!("{} {}", DEATH_EMOJI, death_message);
+        let entities = entity_for(&text, player, Bold).into_iter().collect();
+        self.send_formatted_message(chat_id, &text, entities, false)
+            .await;
+    }
+
+    async fn send_advancement_message(
+        &self,
+        chat_id: i64,
+        player: &str,
+        kind: AdvancementKind,
+        title: &str,
+    ) {
+        let (emoji, text_id) = match kind {
+            AdvancementKind::Advancement => (ADVANCEMENT_EMOJI, "player-advancement"),
+            AdvancementKind::Challenge => (CHALLENGE_EMOJI, "player-challenge"),
+            AdvancementKind::Goal => (GOAL_EMOJI, "player-goal"),
+        };
+        let text = format!(
+            "{} {}",
+            emoji,
+            LOCALES.lookup_with_args(&self.locale, text_id, &{
+                let mut map = HashMap::new();
+                map.insert(
+                    String::from("player"),
+                    FluentValue::String(Cow::from(player)),
+                );
+                map.insert(
+                    String::from("advancement"),
+                    FluentValue::String(Cow::from(title)),
+                );
+                map
+            })
+        );
+        let entities = entity_for(&text, player, Bold).into_iter().collect();
+        self.send_formatted_message(chat_id, &text, entities, false)
+            .await;
+    }
+
+    /// Queues a line of the chat bridge. Lines in quick succession are merged into one message.
+    async fn send_formatted_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+        entities: Vec<MessageEntity>,
+        silent: bool,
+    ) {
+        let send_message_params = SendMessageParams::builder()
+            .chat_id(chat_id)
+            .text(text)
+            .entities(entities)
+            .disable_notification(silent)
+            .build();
+
+        self.outbox.send(chat_id, send_message_params, true);
     }
 
     async fn send_message(&self, chat_id: i64, text: &str) {
@@ -754,8 +1910,255 @@ impl BotData {
             .text(text)
             .build();
 
-        if let Err(err) = self.api.send_message(&send_message_params) {
-            println!("Failed to send message: {:?}", err);
+        self.outbox.send(chat_id, send_message_params, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_source::{FakeLogSource, LogSource};
+    use crate::rcon::RconClient;
+    use crate::service_manager::FakeServiceManager;
+    use tokio::net::TcpListener;
+
+    pub(crate) const CHAT_ID: i64 = 1;
+    pub(crate) const SERVER_NAME: &str = "survival";
+
+    /// An address nobody listens on, so that RCON requests are refused like while the server is
+    /// starting.
+    async fn refusing_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    pub(crate) async fn bot_data(service_manager: FakeServiceManager) -> BotData {
+        let config: Config = serde_json::from_value(json!({
+            "token": "token",
+            "locale": "en-UK",
+            "servers": {
+                SERVER_NAME: {
+                    "display_name": "Survival world",
+                    "rcon_password": "secret",
+                    "idle_shutdown": 30,
+                },
+            },
+            "chat_server_map": { CHAT_ID.to_string(): SERVER_NAME },
+        }))
+        .unwrap();
+        let server = Server::with_backends(
+            RconClient::new(
+                refusing_address().await,
+                String::from("secret"),
+                Duration::from_secs(1),
+            ),
+            Box::new(service_manager),
+            Box::new(FakeLogSource::default()),
+        );
+        let api = AsyncApi::new("token");
+        BotData {
+            locale: LanguageIdentifier::from_str("en-UK").unwrap(),
+            bot_username: String::from("test_bot"),
+            api: api.clone(),
+            config,
+            chatbridge_map: Arc::new(RwLock::new(HashMap::new())),
+            enable_chatbridge_after_start_map: Arc::new(RwLock::new(HashMap::new())),
+            servers: Arc::new(HashMap::from([(String::from(SERVER_NAME), server)])),
+            state: Arc::new(Mutex::new(BotState::default())),
+            outbox: Outbox::new(api),
+            expected_stops: Arc::new(Mutex::new(HashSet::new())),
+            keep_alive: Arc::new(Mutex::new(HashMap::new())),
+            chat_admins: Arc::new(Mutex::new(HashMap::new())),
+            player_list_parser: PlayerListParser::new(),
+        }
+    }
+
+    fn message(text: &str) -> Message {
+        Message::builder()
+            .message_id(1)
+            .date(0)
+            .chat(
+                Chat::builder()
+                    .id(CHAT_ID)
+                    .type_field(ChatType::Group)
+                    .build(),
+            )
+            .text(text)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn stop_inactive_server() {
+        let service_manager = FakeServiceManager::new(false);
+        let mut bot_data = bot_data(service_manager.clone()).await;
+        bot_data
+            .stop_server_handler(message("/stop_server"))
+            .await
+            .unwrap();
+        assert!(service_manager.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stop_starting_server() {
+        // The process runs, but RCON is not available yet.
+        let service_manager = FakeServiceManager::new(true);
+        let mut bot_data = bot_data(service_manager.clone()).await;
+        bot_data
+            .stop_server_handler(message("/stop_server"))
+            .await
+            .unwrap();
+        assert!(service_manager.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stop_server_is_expected() {
+        let service_manager = FakeServiceManager::new(true);
+        let bot_data = bot_data(service_manager.clone()).await;
+        bot_data.stop_server(SERVER_NAME, &[CHAT_ID]).await.unwrap();
+        assert_eq!(service_manager.calls(), vec!["stop"]);
+        assert!(bot_data.take_expected_stop(SERVER_NAME).await);
+    }
+
+    #[tokio::test]
+    async fn texts_use_display_name() {
+        let bot_data = bot_data(FakeServiceManager::new(false)).await;
+        assert_eq!(
+            bot_data.server_text(SERVER_NAME, "stop-server", HashMap::new()),
+            "I stop the server Survival world."
+        );
+    }
+
+    #[tokio::test]
+    async fn chatbridges_reactivated_after_restart() {
+        let service_manager = FakeServiceManager::new(true);
+        let bot_data = bot_data(service_manager.clone()).await;
+        bot_data
+            .chatbridge_map
+            .write()
+            .await
+            .insert(CHAT_ID.to_string(), tokio::spawn(async {}));
+
+        bot_data
+            .reactivate_chatbridges_after_start(SERVER_NAME)
+            .await;
+        bot_data.stop_server(SERVER_NAME, &[CHAT_ID]).await.unwrap();
+        assert!(bot_data.chatbridge_map.read().await.is_empty());
+        let pending = bot_data.enable_chatbridge_after_start_map.read().await;
+        assert_eq!(pending[&CHAT_ID.to_string()].chat.id, CHAT_ID);
+    }
+
+    #[test]
+    fn start_message_status() {
+        let text = start_message_text("I start the server.", "🌍 Preparing the world…");
+        assert_eq!(shown_status(&message(&text)), "🌍 Preparing the world…");
+        let text = start_message_text("I start the server.", "");
+        assert_eq!(text, "I start the server.");
+        assert_eq!(shown_status(&message(&text)), "");
+        let text = start_message_text(
+            "I start the server.",
+            "🌍 Preparing the spawn area…\n▓▓▓▓░░░░░░ 40%",
+        );
+        assert_eq!(
+            shown_status(&message(&text)),
+            "🌍 Preparing the spawn area…\n▓▓▓▓░░░░░░ 40%"
+        );
+    }
+
+    fn user(id: u64) -> User {
+        User::builder()
+            .id(id)
+            .is_bot(false)
+            .first_name("Alex")
+            .build()
+    }
+
+    #[tokio::test]
+    async fn chat_admins_cached() {
+        const GROUP_ID: i64 = -100;
+        let bot_data = bot_data(FakeServiceManager::new(false)).await;
+        bot_data
+            .chat_admins
+            .lock()
+            .await
+            .insert(GROUP_ID, (Instant::now(), HashSet::from([7])));
+
+        // Answered from the cache, the API cannot be reached in tests.
+        assert!(
+            bot_data
+                .user_has_role(GROUP_ID, Some(&user(7)), Role::Operator)
+                .await
+        );
+        assert!(
+            !bot_data
+                .user_has_role(GROUP_ID, Some(&user(8)), Role::Operator)
+                .await
+        );
+        assert!(
+            bot_data
+                .user_has_role(GROUP_ID, Some(&user(8)), Role::Member)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_start_until_done() {
+        // RCON is refused, so only the log can tell that the server is done.
+        let bot_data = bot_data(FakeServiceManager::new(true)).await;
+        let log = FakeLogSource {
+            lines: vec![
+                String::from(r#"[12:00:00] [Server thread/INFO]: Preparing level "world""#),
+                String::from("[12:00:01] [Worker-Main-2/INFO]: Preparing spawn area: 42%"),
+                String::from(
+                    r#"[12:00:02] [Server thread/INFO]: Done (2.345s)! For help, type "help""#,
+                ),
+            ],
+        }
+        .follow()
+        .await
+        .unwrap();
+        let watch = bot_data.watch_start(
+            String::from(SERVER_NAME),
+            message("/start_server"),
+            None,
+            Some(log),
+        );
+        tokio::time::timeout(Duration::from_secs(5), watch)
+            .await
+            .expect("the start was not noticed");
+    }
+
+    #[tokio::test]
+    async fn watch_start_failed() {
+        let bot_data = bot_data(FakeServiceManager::new(false)).await;
+        let log = FakeLogSource {
+            lines: vec![String::from(
+                r#"[12:00:00] [Server thread/INFO]: Preparing level "world""#,
+            )],
         }
+        .follow()
+        .await
+        .unwrap();
+        let watch = bot_data.watch_start(
+            String::from(SERVER_NAME),
+            message("/start_server"),
+            None,
+            Some(log),
+        );
+        tokio::time::timeout(Duration::from_secs(5), watch)
+            .await
+            .expect("the failed start was not noticed");
+    }
+
+    #[tokio::test]
+    async fn keep_alive() {
+        let bot_data = bot_data(FakeServiceManager::new(true)).await;
+        bot_data
+            .keep_alive_handler(message("/keep_alive 3"), Some(3))
+            .await;
+        assert!(bot_data.is_kept_alive(SERVER_NAME).await);
+        bot_data
+            .keep_alive_handler(message("/keep_alive 0"), Some(0))
+            .await;
+        assert!(!bot_data.is_kept_alive(SERVER_NAME).await);
     }
 }
@@ -0,0 +1,374 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Queue for outgoing messages, which keeps to the rate limits of Telegram.
+//!
+//! Telegram allows about one message per second in a private chat, 20 messages per minute in a
+//! group and 30 messages per second overall. Lines of the chat bridge that arrive in quick
+//! succession are merged into one message, so that a busy chat does not fall behind.
+
+use crate::formatting::utf16_len;
+use frankenstein::{AsyncApi, AsyncTelegramApi, Error, SendMessageParams};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep_until, timeout, Instant};
+
+/// Minimum time between two messages to the same private chat.
+const PRIVATE_CHAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Minimum time between two messages to the same group.
+const GROUP_CHAT_INTERVAL: Duration = Duration::from_secs(3);
+/// Minimum time between two messages overall.
+const GLOBAL_INTERVAL: Duration = Duration::from_millis(34);
+/// How long a line of the chat bridge waits for further lines to be merged with.
+const COALESCE_WINDOW: Duration = Duration::from_millis(500);
+/// Maximum length of a message in UTF-16 code units.
+const MAX_MESSAGE_LENGTH: u16 = 4096;
+/// How often sending a message is tried before it is dropped.
+const MAX_SEND_ATTEMPTS: u32 = 5;
+/// Waiting time before retrying a message that failed without a `retry_after`.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// How long sending a message may take before it is given up and retried. Messages are sent one
+/// after another, so a stalled connection would hold up all of them.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+enum OutboxCommand {
+    Send(OutgoingMessage),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug)]
+struct OutgoingMessage {
+    chat_id: i64,
+    params: SendMessageParams,
+    /// Whether the message may be merged with the following ones.
+    coalesce: bool,
+    /// The message is not sent before this time.
+    not_before: Instant,
+    attempts: u32,
+}
+
+impl OutgoingMessage {
+    /// Appends `other` as a new line, if both may be merged and the result is not too long.
+    fn merge(&mut self, other: &OutgoingMessage) -> bool {
+        if !self.coalesce
+            || !other.coalesce
+            || self.params.disable_notification != other.params.disable_notification
+            || u32::from(utf16_len(&self.params.text))
+                + 1
+                + u32::from(utf16_len(&other.params.text))
+                > u32::from(MAX_MESSAGE_LENGTH)
+        {
+            return false;
+        }
+        let offset = utf16_len(&self.params.text) + 1;
+        let entities = other.params.entities.iter().flatten().map(|entity| {
+            let mut entity = entity.clone();
+            entity.offset += offset;
+            entity
+        });
+        self.params
+            .entities
+            .get_or_insert_with(Vec::new)
+            .extend(entities);
+        self.params.text.push('\n');
+        self.params.text.push_str(&other.params.text);
+        true
+    }
+}
+
+/// Handle to the queue, messages are sent by a task of their own.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    sender: mpsc::UnboundedSender<OutboxCommand>,
+}
+
+impl Outbox {
+    pub fn new(api: AsyncApi) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(OutboxWorker::new(api).run(receiver));
+        Outbox { sender }
+    }
+
+    /// Queues a message to `chat_id`. If `coalesce` is set, it may be merged with other messages
+    /// queued with `coalesce` shortly after.
+    pub fn send(&self, chat_id: i64, params: SendMessageParams, coalesce: bool) {
+        let not_before = if coalesce {
+            Instant::now() + COALESCE_WINDOW
+        } else {
+            Instant::now()
+        };
+        let message = OutgoingMessage {
+            chat_id,
+            params,
+            coalesce,
+            not_before,
+            attempts: 0,
+        };
+        if self.sender.send(OutboxCommand::Send(message)).is_err() {
+            println!(
+                "Failed to queue message to {}, the outbox is closed.",
+                chat_id
+            );
+        }
+    }
+
+    /// Waits until all queued messages are sent, but at most for `deadline`.
+    pub async fn flush(&self, deadline: Duration) -> bool {
+        let (sender, receiver) = oneshot::channel();
+        if self.sender.send(OutboxCommand::Flush(sender)).is_err() {
+            return false;
+        }
+        matches!(timeout(deadline, receiver).await, Ok(Ok(())))
+    }
+}
+
+struct OutboxWorker {
+    api: AsyncApi,
+    queues: HashMap<i64, VecDeque<OutgoingMessage>>,
+    /// When the next message may be sent to a chat.
+    chat_next: HashMap<i64, Instant>,
+    global_next: Instant,
+    flush_waiters: Vec<oneshot::Sender<()>>,
+}
+
+impl OutboxWorker {
+    fn new(api: AsyncApi) -> Self {
+        OutboxWorker {
+            api,
+            queues: HashMap::new(),
+            chat_next: HashMap::new(),
+            global_next: Instant::now(),
+            flush_waiters: Vec::new(),
+        }
+    }
+
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<OutboxCommand>) {
+        let mut open = true;
+        loop {
+            if self.queues.is_empty() {
+                for waiter in self.flush_waiters.drain(..) {
+                    let _ = waiter.send(());
+                }
+                if !open {
+                    return;
+                }
+            }
+            match self.next_due() {
+                Some((chat_id, due)) if !open => {
+                    sleep_until(due).await;
+                    self.send_next(chat_id).await;
+                }
+                Some((chat_id, due)) => {
+                    tokio::select! {
+                        command = receiver.recv() => match command {
+                            Some(command) => self.handle(command),
+                            None => open = false,
+                        },
+                        _ = sleep_until(due) => self.send_next(chat_id).await,
+                    }
+                }
+                None => match receiver.recv().await {
+                    Some(command) => self.handle(command),
+                    None => open = false,
+                },
+            }
+        }
+    }
+
+    fn handle(&mut self, command: OutboxCommand) {
+        match command {
+            OutboxCommand::Send(message) => {
+                let queue = self.queues.entry(message.chat_id).or_default();
+                let merged = match queue.back_mut() {
+                    Some(last) => last.merge(&message),
+                    None => false,
+                };
+                if !merged {
+                    queue.push_back(message);
+                }
+            }
+            OutboxCommand::Flush(waiter) => self.flush_waiters.push(waiter),
+        }
+    }
+
+    /// The chat whose next message is due first, and when it is due.
+    fn next_due(&self) -> Option<(i64, Instant)> {
+        self.queues
+            .iter()
+            .filter_map(|(chat_id, queue)| {
+                let message = queue.front()?;
+                let mut due = message.not_before.max(self.global_next);
+                if let Some(chat_next) = self.chat_next.get(chat_id) {
+                    due = due.max(*chat_next);
+                }
+                Some((*chat_id, due))
+            })
+            .min_by_key(|(_, due)| *due)
+    }
+
+    async fn send_next(&mut self, chat_id: i64) {
+        let mut message = match self.queues.get_mut(&chat_id).and_then(VecDeque::pop_front) {
+            Some(message) => message,
+            None => return,
+        };
+        if self.queues.get(&chat_id).is_some_and(VecDeque::is_empty) {
+            self.queues.remove(&chat_id);
+        }
+
+        let result = timeout(SEND_TIMEOUT, self.api.send_message(&message.params)).await;
+        let now = Instant::now();
+        self.global_next = now + GLOBAL_INTERVAL;
+        let interval = if chat_id < 0 {
+            GROUP_CHAT_INTERVAL
+        } else {
+            PRIVATE_CHAT_INTERVAL
+        };
+        self.chat_next.insert(chat_id, now + interval);
+
+        let retry_after = match result {
+            Ok(Ok(_)) => return,
+            Ok(Err(Error::ApiError(response))) => {
+                match response
+                    .parameters
+                    .and_then(|parameters| parameters.retry_after)
+                {
+                    Some(retry_after) => {
+                        println!(
+                            "Too many messages to {}, retrying in {}s.",
+                            chat_id, retry_after
+                        );
+                        Duration::from_secs(u64::from(retry_after))
+                    }
+                    None => {
+                        println!(
+                            "Failed to send message to {}: {}",
+                            chat_id, response.description
+                        );
+                        return;
+                    }
+                }
+            }
+            Ok(Err(err)) => {
+                println!("Failed to send message to {}: {:?}", chat_id, err);
+                RETRY_INTERVAL
+            }
+            Err(_) => {
+                println!(
+                    "Sending message to {} took longer than {}s.",
+                    chat_id,
+                    SEND_TIMEOUT.as_secs()
+                );
+                RETRY_INTERVAL
+            }
+        };
+
+        message.attempts += 1;
+        if message.attempts >= MAX_SEND_ATTEMPTS {
+            println!(
+                "Dropping message to {} after {} attempts.",
+                chat_id, message.attempts
+            );
+            return;
+        }
+        self.chat_next.insert(chat_id, now + retry_after);
+        self.queues.entry(chat_id).or_default().push_front(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frankenstein::{MessageEntity, MessageEntityType};
+
+    fn message(text: &str, bold: Option<(u16, u16)>, coalesce: bool) -> OutgoingMessage {
+        let params = SendMessageParams::builder().chat_id(1).text(text).build();
+        let mut message = OutgoingMessage {
+            chat_id: 1,
+            params,
+            coalesce,
+            not_before: Instant::now(),
+            attempts: 0,
+        };
+        if let Some((offset, length)) = bold {
+            message.params.entities = Some(vec![MessageEntity::builder()
+                .type_field(MessageEntityType::Bold)
+                .offset(offset)
+                .length(length)
+                .build()]);
+        }
+        message
+    }
+
+    fn bold_ranges(message: &OutgoingMessage) -> Vec<(u16, u16)> {
+        message
+            .params
+            .entities
+            .iter()
+            .flatten()
+            .map(|entity| (entity.offset, entity.length))
+            .collect()
+    }
+
+    #[test]
+    fn merge_entity_offsets() {
+        let mut first = message("Alex: hi", Some((0, 4)), true);
+        // The emoji takes two UTF-16 code units.
+        assert!(first.merge(&message("😀 Sam: ok", Some((3, 3)), true)));
+        assert!(first.merge(&message("Kim: bye", Some((0, 3)), true)));
+        assert_eq!(first.params.text, "Alex: hi\n😀 Sam: ok\nKim: bye");
+        assert_eq!(bold_ranges(&first), vec![(0, 4), (12, 3), (20, 3)]);
+    }
+
+    #[test]
+    fn merge_without_entities() {
+        let mut first = message("Server started", None, true);
+        assert!(first.merge(&message("Alex: hi", Some((0, 4)), true)));
+        assert_eq!(bold_ranges(&first), vec![(15, 4)]);
+    }
+
+    #[test]
+    fn merge_length_limit() {
+        let mut first = message(&"a".repeat(2000), None, true);
+        // 2000 + 1 + 2095 units fit exactly.
+        assert!(first.merge(&message(&"b".repeat(2095), None, true)));
+        assert_eq!(utf16_len(&first.params.text), MAX_MESSAGE_LENGTH);
+        assert!(!first.merge(&message("c", None, true)));
+
+        // Counted in UTF-16 code units, not in characters.
+        let mut first = message(&"😀".repeat(1000), None, true);
+        assert!(!first.merge(&message(&"😀".repeat(1048), None, true)));
+        assert!(first.merge(&message(&"😀".repeat(1047), None, true)));
+    }
+
+    #[test]
+    fn merge_disable_notification() {
+        let mut first = message("Alex: hi", None, true);
+        let mut silent = message("Sam: ok", None, true);
+        silent.params.disable_notification = Some(true);
+        assert!(!first.merge(&silent));
+        assert_eq!(first.params.text, "Alex: hi");
+    }
+
+    #[test]
+    fn merge_not_coalescing() {
+        let mut first = message("Alex: hi", None, true);
+        assert!(!first.merge(&message("The server is running.", None, false)));
+        let mut first = message("The server is running.", None, false);
+        assert!(!first.merge(&message("Alex: hi", None, true)));
+    }
+}
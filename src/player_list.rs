@@ -0,0 +1,177 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Parses the reply to the `list` command.
+//!
+//! The known formats are
+//! - `There are 2 of a max of 20 players online: Alice, Bob` (Vanilla, Paper, Forge and Fabric),
+//! - `There are 2/20 players online:Alice, Bob` (Vanilla and Forge before 1.13),
+//! - `There are 2 out of maximum 20 players online.` followed by one line per group like
+//!   `default: Alice, Bob` (Bukkit and EssentialsX) and
+//! - `… online: Alice (069a79f4-44e9-4726-a5be-fca90e38aaf5), …` as reply to `list uuids`.
+
+use crate::formatting::strip_formatting;
+use regex::Regex;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerList {
+    pub online: u32,
+    pub max: u32,
+    pub names: Vec<String>,
+}
+
+/// Markers EssentialsX puts in front of the names of some players.
+const NAME_PREFIXES: &[&str] = &["[AFK]", "[HIDDEN]"];
+
+#[derive(Debug, Clone)]
+pub struct PlayerListParser {
+    header_regex: Regex,
+    uuid_regex: Regex,
+}
+
+impl Default for PlayerListParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlayerListParser {
+    pub fn new() -> Self {
+        PlayerListParser {
+            header_regex: Regex::new(
+                r"There are (\d+) ?(of a max of|out of maximum|/) ?(\d+) players online[:.]?",
+            )
+            .unwrap(),
+            uuid_regex: Regex::new(r" \([0-9a-fA-F]{8}(?:-[0-9a-fA-F]{4}){3}-[0-9a-fA-F]{12}\)$")
+                .unwrap(),
+        }
+    }
+
+    /// Parses the reply to `list` or `list uuids`. Returns `None` if the format is unknown.
+    pub fn parse(&self, reply: &str) -> Option<PlayerList> {
+        // EssentialsX colours the reply.
+        let reply = strip_formatting(reply);
+        let captures = self.header_regex.captures(&reply)?;
+        let online = captures[1].parse().ok()?;
+        let max = captures[3].parse().ok()?;
+        // Bukkit and EssentialsX list the players per group.
+        let grouped = &captures[2] == "out of maximum";
+        let rest = &reply[captures.get(0)?.end()..];
+
+        let names = rest
+            .lines()
+            .map(|line| match line.split_once(": ") {
+                Some((_group, names)) if grouped => names,
+                _ => line,
+            })
+            .flat_map(|names| names.split(','))
+            .map(|name| {
+                let mut name = self.uuid_regex.replace(name.trim(), "").into_owned();
+                for prefix in NAME_PREFIXES {
+                    if let Some(stripped) = name.strip_prefix(prefix) {
+                        name = String::from(stripped.trim_start());
+                    }
+                }
+                name
+            })
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        Some(PlayerList { online, max, names })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player_list(online: u32, max: u32, names: &[&str]) -> Option<PlayerList> {
+        Some(PlayerList {
+            online,
+            max,
+            names: names.iter().map(|name| String::from(*name)).collect(),
+        })
+    }
+
+    #[test]
+    fn vanilla() {
+        let parser = PlayerListParser::new();
+        assert_eq!(
+            parser.parse("There are 2 of a max of 20 players online: Alice, Bob"),
+            player_list(2, 20, &["Alice", "Bob"])
+        );
+        assert_eq!(
+            parser.parse("There are 0 of a max of 20 players online: "),
+            player_list(0, 20, &[])
+        );
+    }
+
+    #[test]
+    fn paper() {
+        assert_eq!(
+            PlayerListParser::new().parse("There are 1 of a max of 50 players online: Steve"),
+            player_list(1, 50, &["Steve"])
+        );
+    }
+
+    #[test]
+    fn essentials() {
+        let parser = PlayerListParser::new();
+        assert_eq!(
+            parser.parse(
+                "§6There are §c3§6 out of maximum §c20§6 players online.\n\
+                 §6default§r: §7[AFK]§rAlice§f, Bob\n\
+                 §6admins§r: Carol"
+            ),
+            player_list(3, 20, &["Alice", "Bob", "Carol"])
+        );
+        assert_eq!(
+            parser.parse("§6There are §c0§6 out of maximum §c20§6 players online."),
+            player_list(0, 20, &[])
+        );
+    }
+
+    #[test]
+    fn forge() {
+        let parser = PlayerListParser::new();
+        assert_eq!(
+            parser.parse("There are 2 of a max of 20 players online: Alice, Bob"),
+            player_list(2, 20, &["Alice", "Bob"])
+        );
+        // Before 1.13.
+        assert_eq!(
+            parser.parse("There are 2/20 players online:Alice, Bob"),
+            player_list(2, 20, &["Alice", "Bob"])
+        );
+    }
+
+    #[test]
+    fn uuids() {
+        assert_eq!(
+            PlayerListParser::new().parse(
+                "There are 2 of a max of 20 players online: \
+                 Alice (069a79f4-44e9-4726-a5be-fca90e38aaf5), \
+                 Bob (853c80ef-3c37-49fd-aa49-938b674adae6)"
+            ),
+            player_list(2, 20, &["Alice", "Bob"])
+        );
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert_eq!(PlayerListParser::new().parse("Unknown command"), None);
+    }
+}
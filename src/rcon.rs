@@ -0,0 +1,385 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A small Source RCON client as spoken by the Minecraft server.
+//!
+//! Every [`RconClient`] keeps one connection to its server open and transparently reconnects
+//! (and re-authenticates) if that connection is lost.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+const PACKET_TYPE_RESPONSE_VALUE: i32 = 0;
+const PACKET_TYPE_EXEC_COMMAND: i32 = 2;
+const PACKET_TYPE_AUTH_RESPONSE: i32 = 2;
+const PACKET_TYPE_AUTH: i32 = 3;
+
+/// Request id the server answers with if the authentication failed.
+const AUTH_FAILED_ID: i32 = -1;
+
+/// Maximum payload length of a packet sent by the client, as accepted by the Minecraft server.
+const MAX_REQUEST_PAYLOAD: usize = 1446;
+
+/// Upper bound for the length field of a received packet, to not allocate arbitrary amounts of
+/// memory if the other side is not an RCON server.
+const MAX_RESPONSE_LENGTH: i32 = 4096 + 10;
+
+#[derive(Debug)]
+pub enum RconError {
+    /// Nobody is listening on the RCON port, e.g. because the server is still starting.
+    ConnectionRefused,
+    /// The server rejected the password.
+    AuthenticationFailed,
+    /// The server did not answer in time.
+    Timeout,
+    /// The command is too long to be sent in a single packet.
+    CommandTooLong,
+    /// The server sent something that is not a valid RCON packet.
+    Protocol(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for RconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RconError::ConnectionRefused => write!(f, "connection refused"),
+            RconError::AuthenticationFailed => write!(f, "authentication failed"),
+            RconError::Timeout => write!(f, "timed out"),
+            RconError::CommandTooLong => write!(f, "command too long"),
+            RconError::Protocol(message) => write!(f, "protocol error: {}", message),
+            RconError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for RconError {}
+
+impl From<io::Error> for RconError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionRefused => RconError::ConnectionRefused,
+            _ => RconError::Io(err),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Packet {
+    id: i32,
+    packet_type: i32,
+    payload: String,
+}
+
+#[derive(Debug)]
+struct RconConnection {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconConnection {
+    async fn connect(address: &str, password: &str) -> Result<Self, RconError> {
+        let stream = TcpStream::connect(address).await?;
+        let mut connection = RconConnection { stream, next_id: 1 };
+        let id = connection.send(PACKET_TYPE_AUTH, password).await?;
+        loop {
+            let packet = connection.receive().await?;
+            if packet.id == AUTH_FAILED_ID {
+                return Err(RconError::AuthenticationFailed);
+            }
+            // Some servers send an empty response value before the actual auth response.
+            if packet.id == id && packet.packet_type == PACKET_TYPE_AUTH_RESPONSE {
+                return Ok(connection);
+            }
+        }
+    }
+
+    async fn execute(&mut self, command: &str) -> Result<String, RconError> {
+        let id = self.send(PACKET_TYPE_EXEC_COMMAND, command).await?;
+        // Long responses are split into several packets without any marker for the last one.
+        // The server answers requests in order, so an invalid request afterwards is used as
+        // terminator: once its answer arrives, the response to the command is complete.
+        let terminator_id = self.send(PACKET_TYPE_RESPONSE_VALUE, "").await?;
+        let mut response = String::new();
+        loop {
+            let packet = self.receive().await?;
+            if packet.id == id {
+                response.push_str(&packet.payload);
+            } else if packet.id == terminator_id {
+                return Ok(response);
+            } else {
+                return Err(RconError::Protocol(format!(
+                    "unexpected response id {}",
+                    packet.id
+                )));
+            }
+        }
+    }
+
+    async fn send(&mut self, packet_type: i32, payload: &str) -> Result<i32, RconError> {
+        if payload.len() > MAX_REQUEST_PAYLOAD {
+            return Err(RconError::CommandTooLong);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+
+        let mut buffer = Vec::with_capacity(payload.len() + 14);
+        buffer.extend_from_slice(&(payload.len() as i32 + 10).to_le_bytes());
+        buffer.extend_from_slice(&id.to_le_bytes());
+        buffer.extend_from_slice(&packet_type.to_le_bytes());
+        buffer.extend_from_slice(payload.as_bytes());
+        buffer.extend_from_slice(&[0, 0]);
+        self.stream.write_all(&buffer).await?;
+        Ok(id)
+    }
+
+    async fn receive(&mut self) -> Result<Packet, RconError> {
+        let length = self.stream.read_i32_le().await?;
+        if !(10..=MAX_RESPONSE_LENGTH).contains(&length) {
+            return Err(RconError::Protocol(format!(
+                "invalid packet length {}",
+                length
+            )));
+        }
+        let mut buffer = vec![0; length as usize];
+        self.stream.read_exact(&mut buffer).await?;
+        let id = i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let packet_type = i32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+        let payload = String::from_utf8_lossy(&buffer[8..buffer.len() - 2]).into_owned();
+        Ok(Packet {
+            id,
+            packet_type,
+            payload,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RconClient {
+    address: String,
+    password: String,
+    timeout: Duration,
+    connection: Mutex<Option<RconConnection>>,
+}
+
+impl RconClient {
+    pub fn new(address: String, password: String, timeout: Duration) -> Self {
+        RconClient {
+            address,
+            password,
+            timeout,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Executes `command` on the server and returns its response.
+    ///
+    /// If the persistent connection broke down since the last command, one new connection is
+    /// established before giving up.
+    pub async fn execute(&self, command: &str) -> Result<String, RconError> {
+        let mut connection = self.connection.lock().await;
+        let reused = connection.is_some();
+        match self.execute_on(&mut connection, command).await {
+            Err(RconError::Io(err)) if reused => {
+                println!(
+                    "RCON connection to {} lost ({}), reconnecting.",
+                    self.address, err
+                );
+                self.execute_on(&mut connection, command).await
+            }
+            result => result,
+        }
+    }
+
+    async fn execute_on(
+        &self,
+        connection: &mut Option<RconConnection>,
+        command: &str,
+    ) -> Result<String, RconError> {
+        let result = timeout(self.timeout, async {
+            if connection.is_none() {
+                *connection = Some(RconConnection::connect(&self.address, &self.password).await?);
+            }
+            connection.as_mut().unwrap().execute(command).await
+        })
+        .await
+        .unwrap_or(Err(RconError::Timeout));
+        if result.is_err() {
+            // The state of the stream is unknown after an error, so never reuse it.
+            *connection = None;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const PASSWORD: &str = "secret";
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<Packet> {
+        let length = stream.read_i32_le().await.ok()?;
+        let mut buffer = vec![0; length as usize];
+        stream.read_exact(&mut buffer).await.ok()?;
+        Some(Packet {
+            id: i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]),
+            packet_type: i32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]),
+            payload: String::from_utf8_lossy(&buffer[8..buffer.len() - 2]).into_owned(),
+        })
+    }
+
+    async fn write_packet(stream: &mut TcpStream, id: i32, packet_type: i32, payload: &str) {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(payload.len() as i32 + 10).to_le_bytes());
+        buffer.extend_from_slice(&id.to_le_bytes());
+        buffer.extend_from_slice(&packet_type.to_le_bytes());
+        buffer.extend_from_slice(payload.as_bytes());
+        buffer.extend_from_slice(&[0, 0]);
+        stream.write_all(&buffer).await.unwrap();
+    }
+
+    /// Answers like a Minecraft server: every command with the packets `reply` returns for it and
+    /// invalid requests with `Unknown request`. The connection is closed after `commands`
+    /// commands, if given.
+    async fn serve(mut stream: TcpStream, reply: fn(&str) -> Vec<String>, commands: Option<usize>) {
+        let mut served = 0;
+        while let Some(packet) = read_packet(&mut stream).await {
+            match packet.packet_type {
+                PACKET_TYPE_AUTH if packet.payload == PASSWORD => {
+                    write_packet(&mut stream, packet.id, PACKET_TYPE_AUTH_RESPONSE, "").await
+                }
+                PACKET_TYPE_AUTH => {
+                    write_packet(&mut stream, AUTH_FAILED_ID, PACKET_TYPE_AUTH_RESPONSE, "").await
+                }
+                PACKET_TYPE_EXEC_COMMAND => {
+                    for part in reply(&packet.payload) {
+                        write_packet(&mut stream, packet.id, PACKET_TYPE_RESPONSE_VALUE, &part)
+                            .await;
+                    }
+                }
+                _ => {
+                    write_packet(
+                        &mut stream,
+                        packet.id,
+                        PACKET_TYPE_RESPONSE_VALUE,
+                        "Unknown request 0",
+                    )
+                    .await;
+                    served += 1;
+                    if commands == Some(served) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts a mock server that handles one connection per entry of `connections`, each closed
+    /// after the given number of commands.
+    async fn mock_server(
+        reply: fn(&str) -> Vec<String>,
+        connections: Vec<Option<usize>>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for commands in connections {
+                let (stream, _) = listener.accept().await.unwrap();
+                serve(stream, reply, commands).await;
+            }
+        });
+        address
+    }
+
+    fn echo(command: &str) -> Vec<String> {
+        vec![format!("Reply to {}", command)]
+    }
+
+    fn client(address: String, password: &str) -> RconClient {
+        RconClient::new(address, String::from(password), Duration::from_secs(2))
+    }
+
+    #[tokio::test]
+    async fn authentication_succeeds() {
+        let address = mock_server(echo, vec![None]).await;
+        let client = client(address, PASSWORD);
+        assert_eq!(client.execute("list").await.unwrap(), "Reply to list");
+        assert_eq!(client.execute("seed").await.unwrap(), "Reply to seed");
+    }
+
+    #[tokio::test]
+    async fn authentication_fails() {
+        let address = mock_server(echo, vec![None]).await;
+        assert!(matches!(
+            client(address, "wrong").execute("list").await,
+            Err(RconError::AuthenticationFailed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn response_split_into_packets() {
+        fn split(_command: &str) -> Vec<String> {
+            vec![String::from("first part, "), String::from("second part")]
+        }
+        let address = mock_server(split, vec![None]).await;
+        assert_eq!(
+            client(address, PASSWORD).execute("help").await.unwrap(),
+            "first part, second part"
+        );
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        // Accepts the connection, but never answers.
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        let client = RconClient::new(address, String::from(PASSWORD), Duration::from_millis(200));
+        assert!(matches!(
+            client.execute("list").await,
+            Err(RconError::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        assert!(matches!(
+            client(address, PASSWORD).execute("list").await,
+            Err(RconError::ConnectionRefused)
+        ));
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_connection_closed() {
+        // The first connection is closed by the server after one command.
+        let address = mock_server(echo, vec![Some(1), None]).await;
+        let client = client(address, PASSWORD);
+        assert_eq!(client.execute("list").await.unwrap(), "Reply to list");
+        assert_eq!(client.execute("seed").await.unwrap(), "Reply to seed");
+    }
+}
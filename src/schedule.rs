@@ -0,0 +1,386 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Runs the actions configured in the schedules of the servers.
+
+use crate::config::{ScheduledAction, ServerConfig};
+use crate::error::BotError;
+use crate::tellraw::text_components;
+use crate::ServerStatus::{Inactive, Running, Starting};
+use crate::{BotData, LOCALES};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use fluent_templates::fluent_bundle::FluentValue;
+use fluent_templates::{LanguageIdentifier, Loader};
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+/// Time between two checks whether the server has started or stopped.
+const STATUS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a stop may take before the chats are told that it takes longer.
+const STOP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What came of a scheduled action, told to the chats of the server.
+enum Outcome {
+    Running,
+    StartingSlowly,
+    /// The server stopped while starting, with a description of its exit status.
+    StartFailed(String),
+    Stopped,
+    StoppingSlowly,
+    /// The action was not run, since the server already is in the state the action leads to or
+    /// the commands would refuse it as well.
+    Skipped(ServerState),
+}
+
+enum ServerState {
+    NotRunning,
+    Starting,
+    Running,
+}
+
+/// The next run of every schedule of the server after `now`, the earliest first. Listing the next
+/// runs regardless of their schedule would only show frequent ones like hourly broadcasts.
+pub fn upcoming_runs(
+    server_config: &ServerConfig,
+    now: DateTime<Tz>,
+) -> Result<Vec<(DateTime<Tz>, &ScheduledAction)>, BotError> {
+    let mut runs = Vec::new();
+    for schedule_config in &server_config.schedules {
+        if let Some(time) = schedule_config.schedule()?.after(&now).next() {
+            runs.push((time, &schedule_config.action));
+        }
+    }
+    runs.sort_by_key(|(time, _)| *time);
+    Ok(runs)
+}
+
+/// Describes the action, e.g. `Restart the server`.
+pub fn action_text(action: &ScheduledAction, locale: &LanguageIdentifier) -> String {
+    match action {
+        ScheduledAction::Start => LOCALES.lookup(locale, "schedule-action-start"),
+        ScheduledAction::Stop => LOCALES.lookup(locale, "schedule-action-stop"),
+        ScheduledAction::Restart => LOCALES.lookup(locale, "schedule-action-restart"),
+        ScheduledAction::Broadcast { message } => {
+            LOCALES.lookup_with_args(locale, "schedule-action-broadcast", &{
+                let mut map = HashMap::new();
+                map.insert(
+                    String::from("message"),
+                    FluentValue::String(Cow::from(message.as_str())),
+                );
+                map
+            })
+        }
+    }
+}
+
+/// Runs the schedule with the given index in the configuration of the server, until the bot shuts
+/// down.
+pub async fn run_schedule(bot_data: BotData, server_name: String, index: usize) {
+    let schedule_config = &bot_data.config.servers[&server_name].schedules[index];
+    let (schedule, time_zone) = match (schedule_config.schedule(), bot_data.config.time_zone()) {
+        (Ok(schedule), Ok(time_zone)) => (schedule, time_zone),
+        (Err(err), _) | (_, Err(err)) => {
+            err.log("schedule", None);
+            return;
+        }
+    };
+
+    let mut last_run = Utc::now().with_timezone(&time_zone);
+    loop {
+        let next = match schedule.after(&last_run).next() {
+            Some(next) => next,
+            None => {
+                println!(
+                    "Schedule {} of server {} has no further runs.",
+                    schedule_config.cron, server_name
+                );
+                return;
+            }
+        };
+        // Negative if the time has passed already.
+        let delay = (next - Utc::now().with_timezone(&time_zone))
+            .to_std()
+            .unwrap_or_default();
+        sleep(delay).await;
+
+        println!(
+            "Run schedule {} of server {}.",
+            schedule_config.cron, server_name
+        );
+        run_action(&bot_data, &server_name, &schedule_config.action).await;
+        // Runs missed while the action took place are skipped.
+        last_run = Utc::now().with_timezone(&time_zone).max(next);
+    }
+}
+
+/// Runs the action and tells the chats of the server about the outcome. Broadcasts are only
+/// reported if they failed, since they usually run often.
+async fn run_action(bot_data: &BotData, server_name: &str, action: &ScheduledAction) {
+    let result = match action {
+        ScheduledAction::Start => start(bot_data, server_name).await,
+        ScheduledAction::Stop => stop(bot_data, server_name).await,
+        ScheduledAction::Restart => restart(bot_data, server_name).await,
+        ScheduledAction::Broadcast { message } => broadcast(bot_data, server_name, message)
+            .await
+            .map(|()| None),
+    };
+    let locale = &bot_data.locale;
+    let outcome = match result {
+        Ok(Some(outcome)) => outcome_text(&outcome, locale),
+        Ok(None) => return,
+        Err(err) => {
+            err.log("schedule", None);
+            LOCALES.lookup(locale, err.text_id())
+        }
+    };
+    let text = format!(
+        "{}\n{}",
+        bot_data.server_text(server_name, "schedule-ran", {
+            let mut map = HashMap::new();
+            map.insert(
+                String::from("action"),
+                FluentValue::String(Cow::from(action_text(action, locale))),
+            );
+            map
+        }),
+        outcome
+    );
+    for chat_id in bot_data.chats_of_server(server_name) {
+        bot_data.send_message(chat_id, &text).await;
+    }
+}
+
+async fn start(bot_data: &BotData, server_name: &str) -> Result<Option<Outcome>, BotError> {
+    match bot_data.get_server_status(server_name).await? {
+        Inactive => {}
+        Starting => return Ok(Some(Outcome::Skipped(ServerState::Starting))),
+        Running { .. } => return Ok(Some(Outcome::Skipped(ServerState::Running))),
+    }
+    bot_data.start_server(server_name).await?;
+    wait_until_started(bot_data, server_name).await.map(Some)
+}
+
+async fn stop(bot_data: &BotData, server_name: &str) -> Result<Option<Outcome>, BotError> {
+    match bot_data.get_server_status(server_name).await? {
+        Inactive => return Ok(Some(Outcome::Skipped(ServerState::NotRunning))),
+        Starting => return Ok(Some(Outcome::Skipped(ServerState::Starting))),
+        Running { .. } => {}
+    }
+    let chat_ids = bot_data.chats_of_server(server_name);
+    bot_data.stop_server(server_name, &chat_ids).await?;
+    wait_until_stopped(bot_data, server_name).await.map(Some)
+}
+
+async fn restart(bot_data: &BotData, server_name: &str) -> Result<Option<Outcome>, BotError> {
+    match bot_data.get_server_status(server_name).await? {
+        Inactive => return Ok(Some(Outcome::Skipped(ServerState::NotRunning))),
+        Starting => return Ok(Some(Outcome::Skipped(ServerState::Starting))),
+        Running { .. } => {}
+    }
+    // Stopping removes the chat bridges, they are activated again once the server is running.
+    bot_data
+        .reactivate_chatbridges_after_start(server_name)
+        .await;
+    let chat_ids = bot_data.chats_of_server(server_name);
+    bot_data.stop_server(server_name, &chat_ids).await?;
+    if let Outcome::StoppingSlowly = wait_until_stopped(bot_data, server_name).await? {
+        return Ok(Some(Outcome::StoppingSlowly));
+    }
+
+    // Otherwise, the watchdog might take the stopped server for one that does not answer anymore.
+    let watchdog = &bot_data.config.servers[server_name].watchdog;
+    if watchdog.enabled {
+        let noticed_within = Duration::from_secs(watchdog.check_interval) + STATUS_CHECK_INTERVAL;
+        let stopped = Instant::now();
+        while bot_data.is_stop_expected(server_name).await && stopped.elapsed() < noticed_within {
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    bot_data.start_server(server_name).await?;
+    wait_until_started(bot_data, server_name).await.map(Some)
+}
+
+async fn broadcast(bot_data: &BotData, server_name: &str, message: &str) -> Result<(), BotError> {
+    if let Inactive | Starting = bot_data.get_server_status(server_name).await? {
+        println!(
+            "Server {} is not running, scheduled broadcast skipped.",
+            server_name
+        );
+        return Ok(());
+    }
+    let mut components = vec![
+        json!(""), // Apparently needed, otherwise the whole message is bold.
+        json!({ "text": bot_data.bot_username, "bold": true }),
+        json!(": "),
+    ];
+    components.extend(text_components(message, &[]));
+    bot_data.broadcast(server_name, components).await
+}
+
+/// Waits until the server answers via RCON, has failed or the start timeout of the server has
+/// passed.
+async fn wait_until_started(bot_data: &BotData, server_name: &str) -> Result<Outcome, BotError> {
+    let start_timeout = Duration::from_secs(bot_data.config.servers[server_name].start_timeout);
+    let started = Instant::now();
+    loop {
+        sleep(STATUS_CHECK_INTERVAL).await;
+        let service_manager = &bot_data.servers[server_name].service_manager;
+        if let Some(exit_status) = service_manager.exit_status().await? {
+            println!("Server {} failed to start: {}", server_name, exit_status);
+            return Ok(Outcome::StartFailed(exit_status));
+        }
+        if let Running { .. } = bot_data.get_server_status(server_name).await? {
+            println!("Server {} started.", server_name);
+            bot_data.activate_pending_chatbridges(server_name).await;
+            return Ok(Outcome::Running);
+        }
+        if started.elapsed() >= start_timeout {
+            println!("Server {} is not started yet.", server_name);
+            bot_data.watch_for_start(server_name).await;
+            return Ok(Outcome::StartingSlowly);
+        }
+    }
+}
+
+async fn wait_until_stopped(bot_data: &BotData, server_name: &str) -> Result<Outcome, BotError> {
+    let stopping = Instant::now();
+    loop {
+        if let Inactive = bot_data.get_server_status(server_name).await? {
+            return Ok(Outcome::Stopped);
+        }
+        if stopping.elapsed() >= STOP_TIMEOUT {
+            println!("Server {} is not stopped yet.", server_name);
+            return Ok(Outcome::StoppingSlowly);
+        }
+        sleep(STATUS_CHECK_INTERVAL).await;
+    }
+}
+
+fn outcome_text(outcome: &Outcome, locale: &LanguageIdentifier) -> String {
+    let text_id = match outcome {
+        Outcome::Running => "schedule-server-running",
+        Outcome::StartingSlowly => "schedule-server-starting-slowly",
+        Outcome::StartFailed(exit_status) => {
+            return LOCALES.lookup_with_args(locale, "schedule-server-start-failed", &{
+                let mut map = HashMap::new();
+                map.insert(
+                    String::from("status"),
+                    FluentValue::String(Cow::from(exit_status.as_str())),
+                );
+                map
+            });
+        }
+        Outcome::Stopped => "schedule-server-stopped",
+        Outcome::StoppingSlowly => "schedule-server-stopping-slowly",
+        Outcome::Skipped(ServerState::NotRunning) => "schedule-skipped-not-running",
+        Outcome::Skipped(ServerState::Starting) => "schedule-skipped-starting",
+        Outcome::Skipped(ServerState::Running) => "schedule-skipped-running",
+    };
+    LOCALES.lookup(locale, text_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScheduleConfig;
+    use chrono::TimeZone;
+
+    fn server_config(schedules: &[(&str, ScheduledAction)]) -> ServerConfig {
+        let mut server_config: ServerConfig = serde_json::from_value(json!({
+            "rcon_password": "password",
+        }))
+        .unwrap();
+        server_config.schedules = schedules
+            .iter()
+            .map(|(cron, action)| ScheduleConfig {
+                cron: String::from(*cron),
+                action: action.clone(),
+            })
+            .collect();
+        server_config
+    }
+
+    /// A time in Berlin, e.g. `berlin(2026, 10, 14, 10, 2)`.
+    fn berlin(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Tz> {
+        chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedules() -> ServerConfig {
+        server_config(&[
+            ("0 18 * * 5", ScheduledAction::Stop),
+            (
+                "*/5 * * * *",
+                ScheduledAction::Broadcast {
+                    message: String::from("Hello"),
+                },
+            ),
+            ("0 4 * * 0", ScheduledAction::Restart),
+        ])
+    }
+
+    #[test]
+    fn upcoming_runs_sorted() {
+        // A Wednesday.
+        let server_config = schedules();
+        let runs = upcoming_runs(&server_config, berlin(2026, 10, 14, 10, 2)).unwrap();
+
+        // One run per schedule, however often it runs.
+        let times = runs.iter().map(|(time, _)| *time).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            vec![
+                berlin(2026, 10, 14, 10, 5),
+                berlin(2026, 10, 16, 18, 0),
+                berlin(2026, 10, 18, 4, 0),
+            ]
+        );
+        assert!(matches!(runs[0].1, ScheduledAction::Broadcast { .. }));
+        assert!(matches!(runs[1].1, ScheduledAction::Stop));
+        assert!(matches!(runs[2].1, ScheduledAction::Restart));
+    }
+
+    #[test]
+    fn upcoming_runs_at_same_time() {
+        // Shortly before the stop on Friday, the broadcast runs at the same time.
+        let server_config = schedules();
+        let runs = upcoming_runs(&server_config, berlin(2026, 10, 16, 17, 57)).unwrap();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].0, berlin(2026, 10, 16, 18, 0));
+        assert_eq!(runs[1].0, berlin(2026, 10, 16, 18, 0));
+        assert_eq!(runs[2].0, berlin(2026, 10, 18, 4, 0));
+    }
+
+    #[test]
+    fn upcoming_runs_none() {
+        let server_config = server_config(&[]);
+        let now = Utc::now().with_timezone(&Tz::UTC);
+        assert!(upcoming_runs(&server_config, now).unwrap().is_empty());
+    }
+
+    #[test]
+    fn upcoming_runs_invalid() {
+        let server_config = server_config(&[("0 18 * * Friday-ish", ScheduledAction::Start)]);
+        let now = Utc::now().with_timezone(&Tz::UTC);
+        assert!(upcoming_runs(&server_config, now).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::config::{LogSourceConfig, ServerConfig, ServiceManagerConfig};
+use crate::log_source::{FileLogSource, JournaldLogSource, LogSource};
+use crate::rcon::RconClient;
+use crate::service_manager::{
+    DockerServiceManager, ProcessServiceManager, ServiceManager, SystemdServiceManager,
+};
+use std::time::Duration;
+
+const RCON_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The connections to a single Minecraft server.
+#[derive(Debug)]
+pub struct Server {
+    pub rcon: RconClient,
+    pub service_manager: Box<dyn ServiceManager>,
+    pub log_source: Box<dyn LogSource>,
+}
+
+impl Server {
+    pub fn new(server_name: &str, config: &ServerConfig) -> Self {
+        let service_manager: Box<dyn ServiceManager> = match &config.service_manager {
+            ServiceManagerConfig::Systemd => {
+                Box::new(SystemdServiceManager::new(config.service_name(server_name)))
+            }
+            ServiceManagerConfig::Docker { container } => {
+                Box::new(DockerServiceManager::new(container.clone()))
+            }
+            ServiceManagerConfig::Process {
+                command,
+                args,
+                working_directory,
+            } => Box::new(ProcessServiceManager::new(
+                command.clone(),
+                args.clone(),
+                working_directory.clone(),
+            )),
+        };
+        let log_source: Box<dyn LogSource> = match &config.log_source {
+            LogSourceConfig::Journald { unit } => Box::new(JournaldLogSource::new(
+                unit.as_ref()
+                    .map(|unit| unit.replace("{}", server_name))
+                    .unwrap_or_else(|| config.service_name(server_name)),
+            )),
+            LogSourceConfig::File { path } => Box::new(FileLogSource::new(path.clone())),
+        };
+        Server::with_backends(
+            RconClient::new(
+                config.rcon_address(),
+                config.rcon_password.clone(),
+                RCON_TIMEOUT,
+            ),
+            service_manager,
+            log_source,
+        )
+    }
+
+    /// Creates a server from the given connections, e.g. fakes in tests.
+    pub fn with_backends(
+        rcon: RconClient,
+        service_manager: Box<dyn ServiceManager>,
+        log_source: Box<dyn LogSource>,
+    ) -> Self {
+        Server {
+            rcon,
+            service_manager,
+            log_source,
+        }
+    }
+}
@@ -0,0 +1,403 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use async_process::{Child, Command as AsyncCommand, Stdio};
+use async_trait::async_trait;
+use futures_lite::AsyncWriteExt;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+/// Time a supervised process gets to shut down after the `stop` command before it is killed.
+const PROCESS_STOP_TIMEOUT: Duration = Duration::from_secs(60);
+/// Time between two checks whether a supervised process has stopped.
+const PROCESS_STOP_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Starts, stops and checks the process a Minecraft server runs in.
+#[async_trait]
+pub trait ServiceManager: Debug + Send + Sync {
+    async fn start(&self) -> io::Result<()>;
+
+    async fn stop(&self) -> io::Result<()>;
+
+    /// Whether the server process is running. This does not say anything about whether the
+    /// server is done starting.
+    async fn is_active(&self) -> io::Result<bool>;
+
+    /// Describes how the server process ended, e.g. with its exit status. Returns `None` while it
+    /// is running or still being started.
+    async fn exit_status(&self) -> io::Result<Option<String>>;
+
+    /// Called when the bot exits. Stops the server if it cannot keep running without the bot.
+    async fn shutdown(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs a command and turns a non-zero exit status into an error.
+async fn run_command(program: &str, args: &[&str]) -> io::Result<String> {
+    let output = AsyncCommand::new(program).args(args).output().await?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io::Error::other(format!(
+            "{} {} failed with {}: {}",
+            program,
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Manages a systemd unit through `sudo systemctl`.
+#[derive(Debug)]
+pub struct SystemdServiceManager {
+    unit: String,
+}
+
+impl SystemdServiceManager {
+    pub fn new(unit: String) -> Self {
+        SystemdServiceManager { unit }
+    }
+}
+
+#[async_trait]
+impl ServiceManager for SystemdServiceManager {
+    async fn start(&self) -> io::Result<()> {
+        run_command("sudo", &["systemctl", "start", &self.unit])
+            .await
+            .map(|_| ())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        run_command("sudo", &["systemctl", "stop", &self.unit])
+            .await
+            .map(|_| ())
+    }
+
+    async fn is_active(&self) -> io::Result<bool> {
+        // is-active exits with a non-zero status for inactive units, so only look at the output.
+        let output = AsyncCommand::new("sudo")
+            .args(["systemctl", "is-active", &self.unit])
+            .output()
+            .await?;
+        Ok(String::from_utf8_lossy(&output.stdout) == "active\n")
+    }
+
+    async fn exit_status(&self) -> io::Result<Option<String>> {
+        let output = run_command(
+            "sudo",
+            &[
+                "systemctl",
+                "show",
+                &self.unit,
+                "--property=ActiveState,Result,ExecMainStatus",
+            ],
+        )
+        .await?;
+        let properties = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect::<HashMap<&str, &str>>();
+        let property = |name| properties.get(name).copied().unwrap_or_default();
+        // A unit that is activating or deactivating has not ended yet.
+        Ok(match property("ActiveState") {
+            "inactive" | "failed" => Some(format!(
+                "Result={}, ExecMainStatus={}",
+                property("Result"),
+                property("ExecMainStatus")
+            )),
+            _ => None,
+        })
+    }
+}
+
+/// Manages a Docker container through the `docker` command line interface.
+#[derive(Debug)]
+pub struct DockerServiceManager {
+    container: String,
+}
+
+impl DockerServiceManager {
+    pub fn new(container: String) -> Self {
+        DockerServiceManager { container }
+    }
+}
+
+#[async_trait]
+impl ServiceManager for DockerServiceManager {
+    async fn start(&self) -> io::Result<()> {
+        run_command("docker", &["start", &self.container])
+            .await
+            .map(|_| ())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        run_command("docker", &["stop", &self.container])
+            .await
+            .map(|_| ())
+    }
+
+    async fn is_active(&self) -> io::Result<bool> {
+        let output = run_command(
+            "docker",
+            &["inspect", "--format", "{{.State.Running}}", &self.container],
+        )
+        .await?;
+        Ok(output.trim() == "true")
+    }
+
+    async fn exit_status(&self) -> io::Result<Option<String>> {
+        let output = run_command(
+            "docker",
+            &[
+                "inspect",
+                "--format",
+                "{{.State.Status}}\t{{.State.ExitCode}}\t{{.State.Error}}",
+                &self.container,
+            ],
+        )
+        .await?;
+        let mut fields = output.trim_end_matches('\n').split('\t');
+        let status = fields.next().unwrap_or_default();
+        let exit_code = fields.next().unwrap_or_default();
+        let error = fields.next().unwrap_or_default();
+        Ok(match status {
+            "exited" | "dead" if error.is_empty() => Some(format!("ExitCode={}", exit_code)),
+            "exited" | "dead" => Some(format!("ExitCode={}, Error={}", exit_code, error)),
+            _ => None,
+        })
+    }
+}
+
+/// Runs the server as a child process of the bot.
+///
+/// The server is stopped by sending `stop` to its console, so the world is saved properly.
+#[derive(Debug)]
+pub struct ProcessServiceManager {
+    command: String,
+    args: Vec<String>,
+    working_directory: Option<PathBuf>,
+    child: Mutex<Option<Child>>,
+}
+
+impl ProcessServiceManager {
+    pub fn new(command: String, args: Vec<String>, working_directory: Option<PathBuf>) -> Self {
+        ProcessServiceManager {
+            command,
+            args,
+            working_directory,
+            child: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl ServiceManager for ProcessServiceManager {
+    async fn start(&self) -> io::Result<()> {
+        let mut child = self.child.lock().await;
+        if let Some(running) = child.as_mut() {
+            if running.try_status()?.is_none() {
+                return Ok(());
+            }
+        }
+        let mut command = AsyncCommand::new(&self.command);
+        command
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(working_directory) = &self.working_directory {
+            command.current_dir(working_directory);
+        }
+        *child = Some(command.spawn()?);
+        Ok(())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        {
+            let mut child = self.child.lock().await;
+            let child = match child.as_mut() {
+                Some(child) => child,
+                None => return Ok(()),
+            };
+            if child.try_status()?.is_some() {
+                return Ok(());
+            }
+            if let Some(stdin) = child.stdin.as_mut() {
+                stdin.write_all(b"stop\n").await?;
+                stdin.flush().await?;
+            }
+        }
+
+        // The lock is only held for a moment at a time, so that the state of the process can be
+        // checked while the server saves the world.
+        let stopping = Instant::now();
+        loop {
+            sleep(PROCESS_STOP_CHECK_INTERVAL).await;
+            let mut child = self.child.lock().await;
+            let child = match child.as_mut() {
+                Some(child) => child,
+                None => return Ok(()),
+            };
+            if child.try_status()?.is_some() {
+                return Ok(());
+            }
+            if stopping.elapsed() >= PROCESS_STOP_TIMEOUT {
+                println!("Process {} did not stop in time, killing it.", self.command);
+                child.kill()?;
+                child.status().await?;
+                return Ok(());
+            }
+        }
+    }
+
+    async fn is_active(&self) -> io::Result<bool> {
+        match self.child.lock().await.as_mut() {
+            Some(child) => Ok(child.try_status()?.is_none()),
+            None => Ok(false),
+        }
+    }
+
+    async fn exit_status(&self) -> io::Result<Option<String>> {
+        match self.child.lock().await.as_mut() {
+            Some(child) => Ok(child.try_status()?.map(|status| status.to_string())),
+            None => Ok(Some(String::from("not running"))),
+        }
+    }
+
+    /// The process would end with the bot without saving the world.
+    async fn shutdown(&self) -> io::Result<()> {
+        self.stop().await
+    }
+}
+
+/// Stands in for a real service manager in tests. Clones share their state, so that a test can
+/// look at the calls made through the clone handed to a [`crate::server::Server`].
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct FakeServiceManager {
+    state: std::sync::Arc<std::sync::Mutex<FakeState>>,
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct FakeState {
+    active: bool,
+    calls: Vec<&'static str>,
+}
+
+#[cfg(test)]
+impl FakeServiceManager {
+    pub fn new(active: bool) -> Self {
+        let fake = FakeServiceManager::default();
+        fake.state.lock().unwrap().active = active;
+        fake
+    }
+
+    /// The names of the methods called so far, e.g. `["start", "stop"]`.
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl ServiceManager for FakeServiceManager {
+    async fn start(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push("start");
+        state.active = true;
+        Ok(())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push("stop");
+        state.active = false;
+        Ok(())
+    }
+
+    async fn is_active(&self) -> io::Result<bool> {
+        Ok(self.state.lock().unwrap().active)
+    }
+
+    async fn exit_status(&self) -> io::Result<Option<String>> {
+        Ok(if self.state.lock().unwrap().active {
+            None
+        } else {
+            Some(String::from("inactive"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A process that exits once it reads `stop`, after taking a moment to "save the world".
+    fn process_service_manager() -> ProcessServiceManager {
+        ProcessServiceManager::new(
+            String::from("sh"),
+            vec![String::from("-c"), String::from("read command; sleep 1")],
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn process_stop() {
+        let service_manager = process_service_manager();
+        service_manager.start().await.unwrap();
+        assert!(service_manager.is_active().await.unwrap());
+        assert_eq!(service_manager.exit_status().await.unwrap(), None);
+
+        service_manager.stop().await.unwrap();
+        assert!(!service_manager.is_active().await.unwrap());
+        assert!(service_manager.exit_status().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn process_state_while_stopping() {
+        let service_manager = std::sync::Arc::new(process_service_manager());
+        service_manager.start().await.unwrap();
+        let stopping = service_manager.clone();
+        let stop = tokio::spawn(async move { stopping.stop().await });
+
+        sleep(Duration::from_millis(100)).await;
+        let is_active =
+            tokio::time::timeout(Duration::from_millis(500), service_manager.is_active())
+                .await
+                .expect("is_active blocked while stopping");
+        assert!(is_active.unwrap());
+
+        stop.await.unwrap().unwrap();
+        assert!(!service_manager.is_active().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn process_shutdown() {
+        let service_manager = process_service_manager();
+        service_manager.start().await.unwrap();
+        service_manager.shutdown().await.unwrap();
+        assert!(!service_manager.is_active().await.unwrap());
+    }
+}
@@ -0,0 +1,153 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::future::{pending, Future};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, watch};
+use tokio::time::timeout;
+
+/// Completes once the bot receives SIGTERM or SIGINT.
+pub async fn shutdown_signal() {
+    let mut terminate =
+        signal(SignalKind::terminate()).expect("Could not register handler for SIGTERM.");
+    let mut interrupt =
+        signal(SignalKind::interrupt()).expect("Could not register handler for SIGINT.");
+    tokio::select! {
+        _ = terminate.recv() => println!("Received SIGTERM."),
+        _ = interrupt.recv() => println!("Received SIGINT."),
+    }
+}
+
+/// Held by a running handler. The handler counts as finished once its guard is dropped.
+#[derive(Debug, Clone)]
+pub struct HandlerGuard {
+    /// Only dropped, see `HandlerTracker::wait`.
+    _finished: mpsc::Sender<()>,
+    abort: watch::Receiver<()>,
+}
+
+impl HandlerGuard {
+    /// Runs the handler in a task of its own, until it is finished or aborted.
+    pub fn spawn(self, handler: impl Future<Output = ()> + Send + 'static) {
+        let mut abort = self.abort.clone();
+        tokio::spawn(async move {
+            let aborted = async {
+                // The tracker is gone without aborting anything.
+                if abort.changed().await.is_err() {
+                    pending::<()>().await;
+                }
+            };
+            tokio::select! {
+                _ = handler => {}
+                _ = aborted => {}
+            }
+            drop(self);
+        });
+    }
+}
+
+/// Keeps track of the running handlers, so that they can finish before the bot exits.
+pub struct HandlerTracker {
+    sender: mpsc::Sender<()>,
+    receiver: mpsc::Receiver<()>,
+    abort: watch::Sender<()>,
+    abort_receiver: watch::Receiver<()>,
+}
+
+impl HandlerTracker {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel(1);
+        let (abort, abort_receiver) = watch::channel(());
+        HandlerTracker {
+            sender,
+            receiver,
+            abort,
+            abort_receiver,
+        }
+    }
+
+    /// Returns the guard for a newly started handler.
+    pub fn track(&self) -> HandlerGuard {
+        HandlerGuard {
+            _finished: self.sender.clone(),
+            abort: self.abort_receiver.clone(),
+        }
+    }
+
+    /// Waits until all handlers are finished, but at most for `deadline`. Handlers still running
+    /// then are aborted at their next await point.
+    ///
+    /// Returns whether all handlers finished in time.
+    pub async fn wait(self, deadline: Duration) -> bool {
+        let HandlerTracker {
+            sender,
+            mut receiver,
+            abort,
+            abort_receiver,
+        } = self;
+        drop(sender);
+        drop(abort_receiver);
+        // Nothing is ever sent, `recv` returns once all guards are dropped.
+        if timeout(deadline, receiver.recv()).await.is_ok() {
+            return true;
+        }
+        // Fails only if no handler is left to abort.
+        let _ = abort.send(());
+        receiver.recv().await;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn finished_handlers() {
+        let tracker = HandlerTracker::new();
+        tracker.track().spawn(sleep(Duration::from_millis(10)));
+        assert!(tracker.wait(Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn aborts_hanging_handlers() {
+        let tracker = HandlerTracker::new();
+        let finished = Arc::new(AtomicBool::new(false));
+        let handler_finished = finished.clone();
+        tracker.track().spawn(async move {
+            sleep(Duration::from_secs(60)).await;
+            handler_finished.store(true, Ordering::SeqCst);
+        });
+        assert!(!tracker.wait(Duration::from_millis(50)).await);
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn tracker_dropped() {
+        let tracker = HandlerTracker::new();
+        let guard = tracker.track();
+        drop(tracker);
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        guard.spawn(async move {
+            sender.send(()).unwrap();
+        });
+        receiver.await.unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Shows the progress of a starting server in the start message.
+
+use crate::events::StartupPhase;
+use crate::LOCALES;
+use fluent_templates::fluent_bundle::FluentValue;
+use fluent_templates::{LanguageIdentifier, Loader};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Minimum time between two edits of the start message, Telegram limits the edits as well.
+const EDIT_INTERVAL: Duration = Duration::from_secs(3);
+
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+/// Decides when the start message is edited to show the current phase.
+#[derive(Debug, Default)]
+pub struct StartupProgress {
+    shown: Option<String>,
+    last_edit: Option<Instant>,
+}
+
+impl StartupProgress {
+    /// Returns the text describing `phase` if the start message should be edited now, i.e. if the
+    /// text changed and the last edit is long enough ago.
+    pub fn update(&mut self, phase: StartupPhase, locale: &LanguageIdentifier) -> Option<String> {
+        let text = phase_text(phase, locale);
+        if self.shown.as_ref() == Some(&text)
+            || self
+                .last_edit
+                .is_some_and(|last_edit| last_edit.elapsed() < EDIT_INTERVAL)
+        {
+            return None;
+        }
+        self.shown = Some(text.clone());
+        self.last_edit = Some(Instant::now());
+        Some(text)
+    }
+}
+
+/// Describes the phase, with a progress bar if the percentage is known.
+fn phase_text(phase: StartupPhase, locale: &LanguageIdentifier) -> String {
+    let (text_id, percent) = match phase {
+        StartupPhase::LoadingMods => ("startup-loading-mods", None),
+        StartupPhase::UpgradingWorld(percent) => ("startup-upgrading-world", percent),
+        StartupPhase::PreparingLevel => ("startup-preparing-level", None),
+        StartupPhase::PreparingSpawn(percent) => ("startup-preparing-spawn", Some(percent)),
+    };
+    let label = LOCALES.lookup(locale, text_id);
+    match percent {
+        Some(percent) => format!("{}\n{}", label, progress_bar(percent)),
+        None => label,
+    }
+}
+
+/// Renders e.g. `▓▓▓▓░░░░░░ 40%`.
+fn progress_bar(percent: u8) -> String {
+    let percent = percent.min(100);
+    let filled = usize::from(percent) * PROGRESS_BAR_WIDTH / 100;
+    format!(
+        "{}{} {}%",
+        "▓".repeat(filled),
+        "░".repeat(PROGRESS_BAR_WIDTH - filled),
+        percent
+    )
+}
+
+/// The line shown once the server is done starting, e.g. `Done (12.345s)`.
+pub fn done_text(startup_time: Duration, locale: &LanguageIdentifier) -> String {
+    LOCALES.lookup_with_args(locale, "startup-done", &{
+        let mut map = HashMap::new();
+        map.insert(
+            String::from("seconds"),
+            FluentValue::String(Cow::from(format!("{:.3}", startup_time.as_secs_f64()))),
+        );
+        map
+    })
+}
@@ -0,0 +1,74 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use frankenstein::Message;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// The state of the bot that survives a restart of the bot.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BotState {
+    /// Chats with an active chat bridge.
+    #[serde(default)]
+    pub chatbridges: Vec<i64>,
+    /// Messages requesting a chat bridge once the server of the chat is started.
+    #[serde(default)]
+    pub pending_chatbridges: Vec<Message>,
+    /// Offset for the next request of updates, all updates before it have been handled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_offset: Option<u32>,
+}
+
+impl BotState {
+    /// Loads the state from `path`. A missing or unreadable file results in an empty state.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+                println!(
+                    "Failed to parse state {}, ignoring it: {}",
+                    path.display(),
+                    err
+                );
+                BotState::default()
+            }),
+            Err(err) if err.kind() == ErrorKind::NotFound => BotState::default(),
+            Err(err) => {
+                println!(
+                    "Failed to read state {}, ignoring it: {}",
+                    path.display(),
+                    err
+                );
+                BotState::default()
+            }
+        }
+    }
+
+    /// Saves the state to `path`.
+    ///
+    /// The state is written to a temporary file first and then moved into place, so that a crash
+    /// while saving does not leave a truncated file behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary_path = path.as_os_str().to_owned();
+        temporary_path.push(".tmp");
+        fs::write(&temporary_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temporary_path, path)
+    }
+}
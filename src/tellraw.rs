@@ -0,0 +1,494 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Builds the JSON text components passed to `tellraw` for messages from Telegram.
+
+use crate::LOCALES;
+use fluent_templates::fluent_bundle::FluentValue;
+use fluent_templates::{LanguageIdentifier, Loader};
+use frankenstein::{Message, MessageEntity, MessageEntityType, User};
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+
+/// Creates the name of the sender, with the full name and username shown when hovering over it.
+pub fn sender_component(user: &User, locale: &LanguageIdentifier) -> Value {
+    let mut full_name = match &user.last_name {
+        Some(last_name) => format!("{} {}", user.first_name, last_name),
+        None => user.first_name.clone(),
+    };
+    if let Some(username) = &user.username {
+        full_name.push_str(&format!(" (@{})", username));
+    }
+    let hover = LOCALES.lookup_with_args(locale, "telegram-sender", &{
+        let mut map = HashMap::new();
+        map.insert(
+            String::from("name"),
+            FluentValue::String(Cow::from(full_name)),
+        );
+        map
+    });
+    json!({
+        "text": user.username.as_ref().unwrap_or(&user.first_name),
+        "bold": true,
+        "hoverEvent": {
+            "action": "show_text",
+            "contents": hover,
+        },
+    })
+}
+
+/// Converts a text and its formatting entities into components with the matching styles.
+///
+/// The text is split at the start and end of every entity, so that each component has the styles
+/// of all entities covering it. This way, nested and overlapping entities keep all their styles.
+pub fn text_components(text: &str, entities: &[MessageEntity]) -> Vec<Value> {
+    // Telegram measures the entities in UTF-16 code units.
+    let units = text.encode_utf16().collect::<Vec<u16>>();
+    let range = |entity: &MessageEntity| {
+        let start = usize::from(entity.offset).min(units.len());
+        let end = (start + usize::from(entity.length)).min(units.len());
+        (start, end)
+    };
+
+    let mut boundaries = BTreeSet::from([0, units.len()]);
+    for entity in entities {
+        let (start, end) = range(entity);
+        boundaries.insert(start);
+        boundaries.insert(end);
+    }
+    let boundaries = boundaries.into_iter().collect::<Vec<usize>>();
+
+    let mut components = Vec::new();
+    for segment in boundaries.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        if start == end {
+            continue;
+        }
+        let mut component = Map::new();
+        component.insert(
+            String::from("text"),
+            json!(String::from_utf16_lossy(&units[start..end])),
+        );
+        for entity in entities {
+            let (entity_start, entity_end) = range(entity);
+            if entity_start <= start && end <= entity_end {
+                let entity_text = String::from_utf16_lossy(&units[entity_start..entity_end]);
+                apply_style(&mut component, entity, &entity_text);
+            }
+        }
+        components.push(Value::Object(component));
+    }
+    components
+}
+
+fn apply_style(component: &mut Map<String, Value>, entity: &MessageEntity, entity_text: &str) {
+    let mut set = |key: &str, value: Value| {
+        component.insert(String::from(key), value);
+    };
+    match entity.type_field {
+        MessageEntityType::Bold => set("bold", json!(true)),
+        MessageEntityType::Italic => set("italic", json!(true)),
+        MessageEntityType::Underline => set("underlined", json!(true)),
+        MessageEntityType::Strikethrough => set("strikethrough", json!(true)),
+        MessageEntityType::Spoiler => set("obfuscated", json!(true)),
+        MessageEntityType::Code | MessageEntityType::Pre => set("font", json!("minecraft:uniform")),
+        MessageEntityType::Url => {
+            // Links without scheme, like `example.org`, are detected by Telegram as well.
+            let url = if entity_text.contains("://") {
+                String::from(entity_text)
+            } else {
+                format!("https://{}", entity_text)
+            };
+            set_link(&mut set, &url);
+        }
+        MessageEntityType::TextLink => {
+            if let Some(url) = &entity.url {
+                set_link(&mut set, url);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn set_link(set: &mut impl FnMut(&str, Value), url: &str) {
+    // Minecraft only opens web links.
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return;
+    }
+    set("color", json!("blue"));
+    set("underlined", json!(true));
+    set(
+        "clickEvent",
+        json!({
+            "action": "open_url",
+            "value": url,
+        }),
+    );
+}
+
+/// Creates a placeholder for the media of the message, e.g. `[Photo]`, with details about the media
+/// shown when hovering over it. Returns `None` for messages without media.
+pub fn media_component(message: &Message, locale: &LanguageIdentifier) -> Option<Value> {
+    let lookup = |text_id: &str, args: &[(&str, String)]| {
+        let args = args
+            .iter()
+            .map(|(key, value)| {
+                (
+                    String::from(*key),
+                    FluentValue::String(Cow::from(value.clone())),
+                )
+            })
+            .collect::<HashMap<String, FluentValue>>();
+        LOCALES.lookup_with_args(locale, text_id, &args)
+    };
+
+    let (label, details) = if let Some(photo) = &message.photo {
+        // The sizes are ordered from small to large.
+        let photo_details = photo
+            .last()
+            .map(|size| {
+                details(
+                    Some(format!("{}×{}", size.width, size.height)),
+                    size.file_size,
+                )
+            })
+            .unwrap_or_default();
+        (lookup("media-photo", &[]), photo_details)
+    } else if let Some(sticker) = &message.sticker {
+        let label = match &sticker.emoji {
+            Some(emoji) => lookup("media-sticker-emoji", &[("emoji", emoji.clone())]),
+            None => lookup("media-sticker", &[]),
+        };
+        let set = match &sticker.set_name {
+            Some(set_name) => lookup("media-sticker-set", &[("set", set_name.clone())]),
+            None => String::new(),
+        };
+        (label, set)
+    } else if let Some(voice) = &message.voice {
+        (
+            lookup(
+                "media-voice",
+                &[("duration", format_duration(voice.duration))],
+            ),
+            details(voice.mime_type.clone(), voice.file_size),
+        )
+    } else if let Some(audio) = &message.audio {
+        let title = match (&audio.performer, &audio.title) {
+            (Some(performer), Some(title)) => Some(format!("{} – {}", performer, title)),
+            (None, Some(title)) => Some(title.clone()),
+            _ => audio.file_name.clone(),
+        };
+        let label = match title {
+            Some(title) => lookup("media-audio-title", &[("title", title)]),
+            None => lookup("media-audio", &[]),
+        };
+        (
+            label,
+            details(Some(format_duration(audio.duration)), audio.file_size),
+        )
+    } else if let Some(video) = &message.video {
+        (
+            lookup(
+                "media-video",
+                &[("duration", format_duration(video.duration))],
+            ),
+            details(
+                Some(format!("{}×{}", video.width, video.height)),
+                video.file_size,
+            ),
+        )
+    } else if let Some(video_note) = &message.video_note {
+        (
+            lookup(
+                "media-video-note",
+                &[("duration", format_duration(video_note.duration))],
+            ),
+            details(None, video_note.file_size),
+        )
+    } else if let Some(animation) = &message.animation {
+        (
+            lookup("media-animation", &[]),
+            details(
+                Some(format!("{}×{}", animation.width, animation.height)),
+                animation.file_size,
+            ),
+        )
+    } else if let Some(document) = &message.document {
+        let label = match &document.file_name {
+            Some(file_name) => lookup("media-document-name", &[("name", file_name.clone())]),
+            None => lookup("media-document", &[]),
+        };
+        (
+            label,
+            details(document.mime_type.clone(), document.file_size),
+        )
+    } else if let Some(poll) = &message.poll {
+        let options = poll
+            .options
+            .iter()
+            .map(|option| format!("• {}", option.text))
+            .collect::<Vec<String>>()
+            .join("\n");
+        (
+            lookup("media-poll", &[("question", poll.question.clone())]),
+            options,
+        )
+    } else if let Some(venue) = &message.venue {
+        (
+            lookup("media-venue", &[("title", venue.title.clone())]),
+            venue.address.clone(),
+        )
+    } else if let Some(location) = &message.location {
+        (
+            lookup("media-location", &[]),
+            format!("{:.5}, {:.5}", location.latitude, location.longitude),
+        )
+    } else if let Some(contact) = &message.contact {
+        let name = match &contact.last_name {
+            Some(last_name) => format!("{} {}", contact.first_name, last_name),
+            None => contact.first_name.clone(),
+        };
+        (lookup("media-contact", &[("name", name)]), String::new())
+    } else if let Some(dice) = &message.dice {
+        (
+            format!("{} {}", dice.emoji, dice.value),
+            lookup("media-dice", &[]),
+        )
+    } else {
+        return None;
+    };
+
+    let mut component = json!({
+        "text": format!("[{}]", label),
+        "color": "gray",
+    });
+    if !details.is_empty() {
+        component["hoverEvent"] = json!({
+            "action": "show_text",
+            "contents": details,
+        });
+    }
+    Some(component)
+}
+
+/// Formats a duration in seconds as `m:ss`.
+fn format_duration(seconds: u32) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Joins the description of the media and its size, if known.
+fn details(description: Option<String>, file_size: Option<u32>) -> String {
+    let size = file_size.map(|size| match size {
+        0..=1023 => format!("{} B", size),
+        1024..=1048575 => format!("{:.1} KiB", f64::from(size) / 1024.0),
+        _ => format!("{:.1} MiB", f64::from(size) / 1048576.0),
+    });
+    description
+        .into_iter()
+        .chain(size)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(type_field: MessageEntityType, offset: u16, length: u16) -> MessageEntity {
+        MessageEntity::builder()
+            .type_field(type_field)
+            .offset(offset)
+            .length(length)
+            .build()
+    }
+
+    #[test]
+    fn overlapping_entities() {
+        let entities = [
+            entity(MessageEntityType::Bold, 0, 9),
+            entity(MessageEntityType::Italic, 5, 6),
+        ];
+        assert_eq!(
+            text_components("bold italic", &entities),
+            vec![
+                json!({"text": "bold ", "bold": true}),
+                json!({"text": "ital", "bold": true, "italic": true}),
+                json!({"text": "ic", "italic": true}),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_link_inside_bold() {
+        let text_link = MessageEntity::builder()
+            .type_field(MessageEntityType::TextLink)
+            .offset(8)
+            .length(4)
+            .url("https://example.org")
+            .build();
+        let entities = [entity(MessageEntityType::Bold, 0, 16), text_link];
+        assert_eq!(
+            text_components("see the wiki now", &entities),
+            vec![
+                json!({"text": "see the ", "bold": true}),
+                json!({
+                    "text": "wiki",
+                    "bold": true,
+                    "color": "blue",
+                    "underlined": true,
+                    "clickEvent": {"action": "open_url", "value": "https://example.org"},
+                }),
+                json!({"text": " now", "bold": true}),
+            ]
+        );
+    }
+
+    #[test]
+    fn utf16_offsets() {
+        // The emoji takes two UTF-16 code units and four bytes.
+        assert_eq!(
+            text_components("😀 bold", &[entity(MessageEntityType::Bold, 3, 4)]),
+            vec![
+                json!({"text": "😀 "}),
+                json!({"text": "bold", "bold": true})
+            ]
+        );
+        assert_eq!(
+            text_components("a😀b", &[entity(MessageEntityType::Italic, 1, 2)]),
+            vec![
+                json!({"text": "a"}),
+                json!({"text": "😀", "italic": true}),
+                json!({"text": "b"}),
+            ]
+        );
+    }
+
+    /// A message with the given media, e.g. `json!({"dice": {"emoji": "🎲", "value": 4}})`.
+    fn media_message(media: Value) -> Message {
+        let mut message = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": -100, "type": "group" },
+        });
+        for (key, value) in media.as_object().unwrap() {
+            message[key] = value.clone();
+        }
+        serde_json::from_value(message).unwrap()
+    }
+
+    fn media(media: Value) -> Option<Value> {
+        media_component(&media_message(media), &"en-UK".parse().unwrap())
+    }
+
+    #[test]
+    fn media_sticker() {
+        let sticker = json!({
+            "file_id": "a",
+            "file_unique_id": "b",
+            "width": 512,
+            "height": 512,
+            "is_animated": false,
+            "is_video": false,
+            "emoji": "😀",
+            "set_name": "Animals",
+        });
+        assert_eq!(
+            media(json!({ "sticker": sticker })),
+            Some(json!({
+                "text": "[Sticker 😀]",
+                "color": "gray",
+                "hoverEvent": { "action": "show_text", "contents": "From the sticker set Animals" },
+            }))
+        );
+    }
+
+    #[test]
+    fn media_voice() {
+        let voice = json!({
+            "file_id": "a",
+            "file_unique_id": "b",
+            "duration": 12,
+            "mime_type": "audio/ogg",
+            "file_size": 20480,
+        });
+        assert_eq!(
+            media(json!({ "voice": voice })),
+            Some(json!({
+                "text": "[Voice message 0:12]",
+                "color": "gray",
+                "hoverEvent": { "action": "show_text", "contents": "audio/ogg, 20.0 KiB" },
+            }))
+        );
+    }
+
+    #[test]
+    fn media_document() {
+        let document = json!({
+            "file_id": "a",
+            "file_unique_id": "b",
+            "file_name": "name.zip",
+            "file_size": 3145728,
+        });
+        assert_eq!(
+            media(json!({ "document": document })),
+            Some(json!({
+                "text": "[File: name.zip]",
+                "color": "gray",
+                "hoverEvent": { "action": "show_text", "contents": "3.0 MiB" },
+            }))
+        );
+    }
+
+    #[test]
+    fn media_photo() {
+        // The largest size is described.
+        let sizes = json!([
+            { "file_id": "a", "file_unique_id": "b", "width": 90, "height": 60, "file_size": 900 },
+            { "file_id": "c", "file_unique_id": "d", "width": 1280, "height": 853 },
+        ]);
+        assert_eq!(
+            media(json!({ "photo": sizes })),
+            Some(json!({
+                "text": "[Photo]",
+                "color": "gray",
+                "hoverEvent": { "action": "show_text", "contents": "1280×853" },
+            }))
+        );
+    }
+
+    #[test]
+    fn media_without_details() {
+        let contact = json!({ "phone_number": "+49", "first_name": "Alex", "last_name": "Doe" });
+        assert_eq!(
+            media(json!({ "contact": contact })),
+            Some(json!({ "text": "[Contact: Alex Doe]", "color": "gray" }))
+        );
+    }
+
+    #[test]
+    fn no_media() {
+        assert_eq!(media(json!({ "text": "hello" })), None);
+    }
+
+    #[test]
+    fn entity_beyond_text() {
+        assert_eq!(
+            text_components("short", &[entity(MessageEntityType::Bold, 2, 100)]),
+            vec![json!({"text": "sh"}), json!({"text": "ort", "bold": true})]
+        );
+    }
+}
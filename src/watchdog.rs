@@ -0,0 +1,340 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Notices when a running server stops unexpectedly, tells its chats and restarts it if
+//! configured.
+
+use crate::error::BotError;
+use crate::events::{EventParser, ServerEvent};
+use crate::failure_report::FailureReport;
+use crate::{BotData, LOCALES};
+use fluent_templates::fluent_bundle::FluentValue;
+use fluent_templates::Loader;
+use frankenstein::SendMessageParams;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::future::pending;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep, Instant, MissedTickBehavior};
+
+/// Waiting time before the first restart, doubled for every further restart within an hour.
+const RESTART_BACKOFF_MIN: Duration = Duration::from_secs(10);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(600);
+/// The period `max_restarts_per_hour` refers to.
+const RESTART_WINDOW: Duration = Duration::from_secs(3600);
+
+enum Crash {
+    /// The process ended, with a description of its exit status.
+    Exited(String),
+    /// The process is still there, but the server does not answer.
+    Unreachable,
+}
+
+enum Step {
+    Line(Option<String>),
+    Check,
+}
+
+/// Watches the server until the bot shuts down.
+pub async fn run_watchdog(bot_data: BotData, server_name: String) {
+    let server_config = &bot_data.config.servers[&server_name];
+    let config = &server_config.watchdog;
+    let server = &bot_data.servers[&server_name];
+    let parser = EventParser::new();
+    let mut check = interval(Duration::from_secs(config.check_interval));
+    check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // Set while the server is known to be running.
+    let mut running_since: Option<SystemTime> = None;
+    let mut log: Option<mpsc::Receiver<String>> = None;
+    let mut report = FailureReport::default();
+    // The log announced a regular stop.
+    let mut stopping = false;
+    let mut rcon_failures = 0;
+    // Set from restarting the server until it is running again.
+    let mut restarting = false;
+    let mut restarts: VecDeque<Instant> = VecDeque::new();
+
+    println!("Watchdog for server {} started.", server_name);
+    loop {
+        let step = tokio::select! {
+            line = next_line(&mut log) => Step::Line(line),
+            _ = check.tick() => Step::Check,
+        };
+        let line = match step {
+            Step::Line(Some(line)) => line,
+            Step::Line(None) => {
+                log = None;
+                continue;
+            }
+            Step::Check => {
+                // Without the state of the process, RCON still tells whether the server runs.
+                let exit_status = match server.service_manager.exit_status().await {
+                    Ok(exit_status) => exit_status,
+                    Err(err) => {
+                        BotError::from(err).log("watchdog", None);
+                        None
+                    }
+                };
+                let crash = match exit_status {
+                    Some(exit_status) => {
+                        let crash = if running_since.is_none() && !restarting {
+                            None
+                        } else if stopping || bot_data.take_expected_stop(&server_name).await {
+                            println!("Server {} stopped.", server_name);
+                            None
+                        } else {
+                            Some(Crash::Exited(exit_status))
+                        };
+                        running_since = None;
+                        restarting = false;
+                        crash
+                    }
+                    None => match server.rcon.execute("list").await {
+                        Ok(_) => {
+                            rcon_failures = 0;
+                            if running_since.is_none() {
+                                println!("Watchdog sees server {} running.", server_name);
+                                running_since = Some(SystemTime::now());
+                                bot_data.take_expected_stop(&server_name).await;
+                                report = FailureReport::default();
+                                log = match server.log_source.follow().await {
+                                    Ok(log) => Some(log),
+                                    Err(err) => {
+                                        println!(
+                                            "Failed to follow log of server {:}: {}",
+                                            server_name, err
+                                        );
+                                        None
+                                    }
+                                };
+                                if restarting {
+                                    restarting = false;
+                                    send_to_chats(
+                                        &bot_data,
+                                        &server_name,
+                                        &LOCALES.lookup(&bot_data.locale, "watchdog-restarted"),
+                                    )
+                                    .await;
+                                }
+                            }
+                            None
+                        }
+                        Err(_) if running_since.is_some() && !stopping => {
+                            rcon_failures += 1;
+                            println!(
+                                "Server {} did not answer via RCON, {} time(s) in a row.",
+                                server_name, rcon_failures
+                            );
+                            if rcon_failures >= config.rcon_failures {
+                                Some(Crash::Unreachable)
+                            } else {
+                                None
+                            }
+                        }
+                        Err(_) => None,
+                    },
+                };
+
+                if running_since.is_none() || crash.is_some() {
+                    log = None;
+                    stopping = false;
+                    rcon_failures = 0;
+                }
+                if let Some(crash) = crash {
+                    report_crash(
+                        &bot_data,
+                        &server_name,
+                        &crash,
+                        running_since.take(),
+                        &report,
+                    );
+                    if config.restart {
+                        restarting = restart(&bot_data, &server_name, &crash, &mut restarts).await;
+                    }
+                }
+                continue;
+            }
+        };
+
+        let event = parser.parse(&line);
+        report.record(&line, event.as_ref());
+        if let Some(ServerEvent::Stopping) = event {
+            stopping = true;
+        }
+    }
+}
+
+async fn next_line(log: &mut Option<mpsc::Receiver<String>>) -> Option<String> {
+    match log {
+        Some(log) => log.recv().await,
+        None => pending().await,
+    }
+}
+
+/// Tells the chats of the server about the crash. `running_since` is when the server was seen
+/// running first, crash reports older than that are from earlier crashes.
+fn report_crash(
+    bot_data: &BotData,
+    server_name: &str,
+    crash: &Crash,
+    running_since: Option<SystemTime>,
+    report: &FailureReport,
+) {
+    let locale = &bot_data.locale;
+    let reason = match crash {
+        Crash::Exited(exit_status) => exit_status.clone(),
+        Crash::Unreachable => LOCALES.lookup_with_args(locale, "watchdog-unreachable", &{
+            let mut map = HashMap::new();
+            map.insert(
+                String::from("checks"),
+                FluentValue::from(bot_data.config.servers[server_name].watchdog.rcon_failures),
+            );
+            map
+        }),
+    };
+    println!("Server {} crashed: {}", server_name, reason);
+
+    let mut headline = bot_data.server_text(server_name, "server-crashed", {
+        let mut map = HashMap::new();
+        map.insert(
+            String::from("reason"),
+            FluentValue::String(Cow::from(reason)),
+        );
+        map
+    });
+    let crash_report = bot_data.config.servers[server_name]
+        .crash_reports()
+        .and_then(|directory| newest_crash_report(&directory, running_since));
+    if let Some(crash_report) = crash_report {
+        headline.push('\n');
+        headline.push_str(&LOCALES.lookup_with_args(locale, "crash-report", &{
+            let mut map = HashMap::new();
+            map.insert(
+                String::from("file"),
+                FluentValue::String(Cow::from(crash_report)),
+            );
+            map
+        }));
+    }
+
+    let (text, entities) = report.format(&headline, locale);
+    for chat_id in bot_data.chats_of_server(server_name) {
+        let send_message_params = SendMessageParams::builder()
+            .chat_id(chat_id)
+            .text(&text)
+            .entities(entities.clone())
+            .build();
+        bot_data.outbox.send(chat_id, send_message_params, false);
+    }
+}
+
+/// Restarts the server after a delay growing with the number of recent restarts. Returns whether
+/// the server was restarted.
+async fn restart(
+    bot_data: &BotData,
+    server_name: &str,
+    crash: &Crash,
+    restarts: &mut VecDeque<Instant>,
+) -> bool {
+    let max_restarts = bot_data.config.servers[server_name]
+        .watchdog
+        .max_restarts_per_hour;
+    while restarts
+        .front()
+        .is_some_and(|restart| restart.elapsed() > RESTART_WINDOW)
+    {
+        restarts.pop_front();
+    }
+    if restarts.len() >= max_restarts as usize {
+        println!(
+            "Server {} was restarted {} times within an hour, not restarting it again.",
+            server_name,
+            restarts.len()
+        );
+        let text = LOCALES.lookup_with_args(&bot_data.locale, "watchdog-restart-limit", &{
+            let mut map = HashMap::new();
+            map.insert(String::from("restarts"), FluentValue::from(restarts.len()));
+            map
+        });
+        send_to_chats(bot_data, server_name, &text).await;
+        return false;
+    }
+
+    let delay = RESTART_BACKOFF_MIN
+        .saturating_mul(2u32.saturating_pow(restarts.len() as u32))
+        .min(RESTART_BACKOFF_MAX);
+    let text = LOCALES.lookup_with_args(&bot_data.locale, "watchdog-restarting", &{
+        let mut map = HashMap::new();
+        map.insert(String::from("seconds"), FluentValue::from(delay.as_secs()));
+        map
+    });
+    send_to_chats(bot_data, server_name, &text).await;
+    sleep(delay).await;
+
+    let service_manager = &bot_data.servers[server_name].service_manager;
+    // A server that does not answer anymore has to be stopped first.
+    if let Crash::Unreachable = crash {
+        if let Err(err) = service_manager.stop().await {
+            BotError::from(err).log("watchdog", None);
+        }
+    }
+    println!("Restart server {}.", server_name);
+    restarts.push_back(Instant::now());
+    match service_manager.start().await {
+        Ok(()) => true,
+        Err(err) => {
+            BotError::from(err).log("watchdog", None);
+            false
+        }
+    }
+}
+
+async fn send_to_chats(bot_data: &BotData, server_name: &str, text: &str) {
+    for chat_id in bot_data.chats_of_server(server_name) {
+        bot_data.send_message(chat_id, text).await;
+    }
+}
+
+/// The name of the newest file in `directory`, if it was written after `since`.
+fn newest_crash_report(directory: &Path, since: Option<SystemTime>) -> Option<String> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            println!(
+                "Failed to read crash reports in {}: {}",
+                directory.display(),
+                err
+            );
+            return None;
+        }
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            Some((metadata.modified().ok()?, entry.file_name()))
+        })
+        .filter(|(modified, _)| since.is_none_or(|since| *modified >= since))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, file_name)| file_name.to_string_lossy().into_owned())
+}
@@ -0,0 +1,267 @@
+/* Copyright (C) 2022    Joshua Noeske
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Receives updates via a webhook, as an alternative to polling for them.
+
+use crate::config::WebhookConfig;
+use crate::error::BotError;
+use crate::shutdown::{HandlerGuard, HandlerTracker};
+use crate::{BotData, ALLOWED_UPDATES};
+use frankenstein::{AsyncTelegramApi, MethodResponse, Update};
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::future::Future;
+
+/// Header Telegram sends the secret token of the webhook in.
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Requests with a larger body are rejected, updates are much smaller.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Parameters of `setWebhook`. Those of frankenstein lack the secret token.
+#[derive(Debug, Serialize)]
+struct SetWebhookParams {
+    url: String,
+    secret_token: String,
+    allowed_updates: Vec<String>,
+}
+
+/// Registers the webhook and handles the updates sent to it, until `shutdown` completes.
+///
+/// Fails if the webhook cannot be set up, errors later on are only logged.
+pub async fn run_webhook(
+    bot_data: &BotData,
+    webhook: &WebhookConfig,
+    handler_tracker: &HandlerTracker,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), BotError> {
+    let params = SetWebhookParams {
+        url: webhook.url.clone(),
+        secret_token: webhook.secret_token.clone(),
+        allowed_updates: ALLOWED_UPDATES.map(String::from).to_vec(),
+    };
+    bot_data
+        .api
+        .request::<_, MethodResponse<bool>>("setWebhook", Some(params))
+        .await?;
+
+    let bot_data = bot_data.clone();
+    let secret_token = webhook.secret_token.clone();
+    let guard = handler_tracker.track();
+    let make_service = make_service_fn(move |_| {
+        let bot_data = bot_data.clone();
+        let secret_token = secret_token.clone();
+        let guard = guard.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(
+                    bot_data.clone(),
+                    secret_token.clone(),
+                    guard.clone(),
+                    request,
+                )
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&webhook.listen)
+        .map_err(|err| {
+            BotError::Config(format!("could not listen on {}: {}", webhook.listen, err))
+        })?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown);
+    println!("Listening for updates on {}.", webhook.listen);
+    if let Err(err) = server.await {
+        println!("Webhook server failed: {}", err);
+    }
+    Ok(())
+}
+
+async fn handle_request(
+    bot_data: BotData,
+    secret_token: String,
+    guard: HandlerGuard,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let authorised = request
+        .headers()
+        .get(SECRET_TOKEN_HEADER)
+        .map(|token| constant_time_eq(token.as_bytes(), secret_token.as_bytes()))
+        .unwrap_or(false);
+    if !authorised {
+        println!("Rejected webhook request without valid secret token.");
+        return Ok(status(StatusCode::UNAUTHORIZED));
+    }
+    let body = match read_body(request.into_body()).await {
+        Ok(body) => body,
+        Err(status_code) => return Ok(status(status_code)),
+    };
+    match serde_json::from_slice::<Update>(&body) {
+        Ok(update) => {
+            bot_data.dispatch_update(update, guard);
+            Ok(status(StatusCode::OK))
+        }
+        Err(err) => {
+            println!("Failed to parse update from webhook: {}", err);
+            Ok(status(StatusCode::BAD_REQUEST))
+        }
+    }
+}
+
+/// Reads the body, but at most `MAX_BODY_SIZE` bytes. The size of chunked bodies is only known
+/// once they are read.
+async fn read_body(mut body: Body) -> Result<Vec<u8>, StatusCode> {
+    if body.size_hint().lower() > MAX_BODY_SIZE {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| {
+            println!("Failed to read webhook request: {}", err);
+            StatusCode::BAD_REQUEST
+        })?;
+        if (bytes.len() + chunk.len()) as u64 > MAX_BODY_SIZE {
+            println!(
+                "Rejected webhook request larger than {} bytes.",
+                MAX_BODY_SIZE
+            );
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Compares in a time independent of where the inputs differ, so that the token cannot be guessed
+/// byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |result, (a, b)| result | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service_manager::FakeServiceManager;
+    use crate::tests::{bot_data, CHAT_ID, SERVER_NAME};
+    use serde_json::json;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tokio::time::sleep;
+
+    const SECRET_TOKEN: &str = "webhook-secret";
+
+    fn update(text: &str) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 1,
+                "date": now,
+                "chat": { "id": CHAT_ID, "type": "group" },
+                "text": text,
+            },
+        })
+        .to_string()
+    }
+
+    fn request(method: Method, secret_token: Option<&str>, body: Body) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri("/");
+        if let Some(secret_token) = secret_token {
+            builder = builder.header(SECRET_TOKEN_HEADER, secret_token);
+        }
+        builder.body(body).unwrap()
+    }
+
+    async fn handle(request: Request<Body>) -> (StatusCode, BotData) {
+        let bot_data = bot_data(FakeServiceManager::new(true)).await;
+        let response = handle_request(
+            bot_data.clone(),
+            String::from(SECRET_TOKEN),
+            HandlerTracker::new().track(),
+            request,
+        )
+        .await
+        .unwrap();
+        (response.status(), bot_data)
+    }
+
+    #[tokio::test]
+    async fn missing_secret_token() {
+        let body = Body::from(update("/keep_alive"));
+        let (status, _) = handle(request(Method::POST, None, body)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn wrong_secret_token() {
+        let body = Body::from(update("/keep_alive"));
+        let (status, _) = handle(request(Method::POST, Some("guessed"), body)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn not_post() {
+        let (status, _) = handle(request(Method::GET, Some(SECRET_TOKEN), Body::empty())).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn invalid_update() {
+        let body = Body::from("{}");
+        let (status, _) = handle(request(Method::POST, Some(SECRET_TOKEN), body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn chunked_body_too_large() {
+        // Chunked bodies do not tell their size in advance.
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            let chunk = vec![b' '; 64 * 1024];
+            while sender.send_data(chunk.clone().into()).await.is_ok() {}
+        });
+        let (status, _) = handle(request(Method::POST, Some(SECRET_TOKEN), body)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn update_dispatched() {
+        let body = Body::from(update("/keep_alive 3"));
+        let (status, bot_data) = handle(request(Method::POST, Some(SECRET_TOKEN), body)).await;
+        assert_eq!(status, StatusCode::OK);
+        for _ in 0..50 {
+            if bot_data.is_kept_alive(SERVER_NAME).await {
+                return;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        panic!("update was not handled");
+    }
+}